@@ -1,12 +1,25 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
-use crate::GameState;
+use crate::{
+    clock::ShowUpdate,
+    men::MenState,
+    movement::{MovementCommandsExt, MovementPath, PathInterpolation, PathMode},
+    shared::{random_pos, ShowRng},
+    GameState
+};
 
 pub struct AirplanePlugin;
 impl Plugin for AirplanePlugin {
     fn build(&self, app: &mut App) {
         app
+        .init_resource::<DropSchedule>()
+        .add_event::<ManDropped>()
         .add_systems(OnEnter(GameState::Raining), spawn)
-        .add_systems(Update, fly.run_if(in_state(GameState::Raining)))
+        .add_systems(ShowUpdate, drop_men
+            .run_if(in_state(MenState::Moving))
+            .run_if(any_with_component::<AirPlane>)
+        )
         .enable_state_scoped_entities::<GameState>()
         ;
     }
@@ -17,31 +30,130 @@ impl Plugin for AirplanePlugin {
 #[derive(Component)]
 pub struct AirPlane;
 
+// men still on board, they stay hidden until the plane lets them go
+
+#[derive(Component)]
+pub struct Aboard;
+
+// men leave the plane in batches, the first batch `delay` seconds after the plane shows up,
+// insert the resource before AirplanePlugin to change it
+
+#[derive(Resource, Clone, Copy)]
+pub struct DropSchedule {
+    pub delay: f32,
+    pub interval: f32,
+    pub batch: usize
+}
+
+impl Default for DropSchedule {
+    fn default() -> Self {
+        DropSchedule {
+            delay: 2.,
+            interval: 0.5,
+            batch: 2
+        }
+    }
+}
+
+impl DropSchedule {
+    fn due(&self, elapsed: f32) -> usize {
+        if elapsed < self.delay {
+            return 0;
+        }
+        (((elapsed - self.delay) / self.interval.max(f32::EPSILON)).floor() as usize + 1) * self.batch
+    }
+
+    // seconds from one drop until the turn of the `k`-th man still aboard
+    pub fn wait(&self, k: usize) -> f32 {
+        (k / self.batch.max(1) + 1) as f32 * self.interval
+    }
+}
+
+// where the plane is `dt` seconds on from `pos`, flying its circle around the origin at `velocity`
+
+pub fn ahead(pos: Vec3, velocity: Vec3, dt: f32) -> Vec3 {
+    let r = pos.xz();
+    if r.length_squared() < f32::EPSILON {
+        return pos + velocity * dt;
+    }
+    let turn = r.perp_dot(velocity.xz()) / r.length_squared() * dt;
+    let r = Vec2::from_angle(turn).rotate(r);
+    Vec3::new(r.x, pos.y, r.y)
+}
+
+// man and the plane velocity at the moment of the jump
+
+#[derive(Event)]
+pub struct ManDropped(pub Entity, pub Vec3);
+
+#[derive(Component)]
+struct Hatch {
+    elapsed: f32,
+    dropped: usize,
+    last: Vec3
+}
+
+const ROUTE_RADIUS: f32 = 80.;
+const ROUTE_HEIGHT: f32 = 120.;
+const ROUTE_POINTS: usize = 8;
+const AIRPLANE_VELOCITY: f32 = 20.;
+const HATCH_OFFSET: Vec3 = Vec3::new(0., -2., 0.);
+const HATCH_SPREAD: f32 = 1.;
+
 // --
 
 fn spawn(
     mut commands: Commands, 
     assets: ResMut<AssetServer>
 ) {
+    let path = (1 ..= ROUTE_POINTS).fold(MovementPath::builder(), |path, i| {
+        let a = TAU * i as f32 / ROUTE_POINTS as f32;
+        path.to(Vec3::new(ROUTE_RADIUS * a.cos(), ROUTE_HEIGHT, ROUTE_RADIUS * a.sin()), AIRPLANE_VELOCITY)
+    });
+    let start = Vec3::new(ROUTE_RADIUS, ROUTE_HEIGHT, 0.);
+
     commands.spawn((
         SceneBundle {
             scene: assets.load("models/other/airplane.glb#Scene0"),
-            transform: Transform::from_xyz(0., 120., 0.),
+            transform: Transform::from_translation(start),
             ..default()
         },
         AirPlane,
+        Hatch {elapsed: 0., dropped: 0, last: start},
         StateScoped(GameState::Raining)
-    ));
+    ))
+    .start_path(
+        path
+        .mode(PathMode::Loop)
+        .interpolation(PathInterpolation::CatmullRom)
+    );
 }
 
-// ---
+// --
 
-fn fly (
-    mut jet_q : Query<&mut Transform, With<AirPlane>>,
+fn drop_men(
+    mut plane_q: Query<(&Transform, &mut Hatch), With<AirPlane>>,
+    mut men_q: Query<(Entity, &mut Transform, &mut Visibility), (With<Aboard>, Without<AirPlane>)>,
+    schedule: Res<DropSchedule>,
+    mut dropped_w: EventWriter<ManDropped>,
+    mut rng: ResMut<ShowRng>,
+    mut cmd: Commands,
     time: Res<Time>
 ) {
-    if let Ok(mut t) = jet_q.get_single_mut() {
-        let m = t.forward() * time.delta_seconds() * 20.;
-        t.translation += m;
+    let Ok((plane_t, mut hatch)) = plane_q.get_single_mut() else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    let velocity = if dt > 0. {(plane_t.translation - hatch.last) / dt} else {Vec3::ZERO};
+    hatch.last = plane_t.translation;
+    hatch.elapsed += dt;
+
+    let due = schedule.due(hatch.elapsed);
+    for (e, mut t, mut vis) in men_q.iter_mut().take(due.saturating_sub(hatch.dropped)) {
+        t.translation = random_pos(&mut rng.0, plane_t.translation + HATCH_OFFSET, HATCH_SPREAD);
+        *vis = Visibility::Visible;
+        cmd.entity(e).remove::<Aboard>();
+        dropped_w.send(ManDropped(e, velocity));
+        hatch.dropped += 1;
     }
-}
\ No newline at end of file
+}
@@ -1,16 +1,255 @@
+use bevy::animation::{AnimationTarget, AnimationTargetId, RepeatAnimation};
+use bevy::asset::LoadState;
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use std::time::Duration;
-use crate::{GameState, NotReady};
+use crate::{
+    movement::{MovementPath, MovementPaused},
+    shared::{entity_rng, ShowSeed},
+    GameState, NotReady
+};
 
 // ---
 
 #[derive(Component)]
-pub struct  CurrentAnimation(pub usize, Entity);
+pub struct  CurrentAnimation(pub Clip, Entity, Playback);
+
+// what the next switch does besides playing `.0`
+
+#[derive(Default, Clone, Copy)]
+struct Playback {
+    blend: Option<Duration>,
+    layer: Option<Layer>,
+    once: Option<OneShot>
+}
+
+// a clip played a single time, `node` is set once it has started,
+// `back` is the looping clip to return to when it ends
+
+#[derive(Clone, Copy)]
+struct OneShot {
+    node: Option<AnimationNodeIndex>,
+    back: Option<Clip>
+}
+
+// a clip played over the main one, only on the bones under `mask`,
+// e.g. arms waving from "mixamorig:Spine1" up while the legs keep walking
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    pub clip: Clip,
+    pub mask: &'static str
+}
+
+impl CurrentAnimation {
+    // like setting `.0`, but with its own crossfade instead of the one registered for the pair
+
+    pub fn crossfade(&mut self, clip: impl Into<Clip>, blend: Duration) {
+        self.0 = clip.into();
+        self.2.blend = Some(blend);
+    }
+
+    // `clip` plays once and AnimationFinished is sent at its end, with `back`
+    // the looping clip from before is played again, a chain of one-shots returns
+    // to the clip from before the first of them
+
+    pub fn play_once(&mut self, clip: impl Into<Clip>, back: bool) {
+        let looping = self.2.once.and_then(|o| o.back).unwrap_or(self.0);
+        self.0 = clip.into();
+        self.2.once = Some(OneShot {node: None, back: back.then_some(looping)});
+    }
+
+    pub fn set_layer(&mut self, clip: impl Into<Clip>, mask: &'static str) {
+        self.2.layer = Some(Layer {clip: clip.into(), mask});
+    }
+
+    pub fn clear_layer(&mut self) {
+        self.2.layer = None;
+    }
+
+    pub fn layer(&self) -> Option<Layer> {
+        self.2.layer
+    }
+}
+
+// the layer clip outweighs the main one on the masked bones,
+// bones it has no curves for are left to the main clip alone
+
+#[derive(Component, Default)]
+pub struct Layers {
+    active: Option<(AnimationNodeIndex, f32)>,
+    fading: Vec<(AnimationNodeIndex, f32)>
+}
+
+// the key's walk and run clips with the ground speed each of them is made for,
+// between the two speeds both play, weighted by where the real speed lies
+
+#[derive(Clone, Copy, Debug)]
+pub struct Locomotion {
+    pub walk: Clip,
+    pub walk_speed: f32,
+    pub run: Clip,
+    pub run_speed: f32
+}
+
+// ground speed of the character's path and the clip blended into the main one
+
+#[derive(Component, Default)]
+pub struct Gait {
+    pub speed: f32,
+    partner: Option<AnimationNodeIndex>
+}
+
+// a one-shot clip has played to its end,
+// not sent when the clip was replaced before that
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct AnimationFinished(pub Entity, pub Clip);
+
+// a marker was passed by one of the clips playing on the entity,
+// a clip fading in or out counts while it weighs at least MARKER_MIN_WEIGHT
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct AnimationMarker(pub Entity, pub &'static str);
+
+// where every clip was at the previous check: elapsed, seek time, completions
+
+#[derive(Component, Default)]
+pub struct MarkerClock(HashMap<AnimationNodeIndex, (f32, f32, u32)>);
+
+// every looping clip starts at a random point of its cycle (`random_phase`),
+// every character plays a bit faster or slower, up to `speed_jitter`
+
+#[derive(Clone, Copy, Debug)]
+pub struct Variety {
+    pub random_phase: bool,
+    pub speed_jitter: f32
+}
+
+// the character's own playback rate, the main clip it was last applied to
+// and the character's own random numbers for the phases
+
+#[derive(Component)]
+pub struct Desync {
+    rate: f32,
+    main: Option<AnimationNodeIndex>,
+    rng: fastrand::Rng
+}
+
+impl Desync {
+    fn new(variety: Option<Variety>, mut rng: fastrand::Rng) -> Self {
+        Desync {
+            rate: variety.map_or(1., |v| 1. + v.speed_jitter * (rng.f32() * 2. - 1.)),
+            main: None,
+            rng
+        }
+    }
+}
+
+const DEFAULT_BLEND: Duration = Duration::from_millis(250);
+const LAYER_FADE: f32 = 0.25;
+const MAX_LAYER_SHARE: f32 = 0.99;
+const SPEED_SMOOTHING: f32 = 8.;
+const MIN_PLAYBACK_SPEED: f32 = 0.3;
+const MAX_PLAYBACK_SPEED: f32 = 3.;
+const MIN_GAIT_SHARE: f32 = 0.01;
+const MARKER_MIN_WEIGHT: f32 = 0.5;
+
+// a clip either by its position in the glTF or by its name there
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clip {
+    Index(usize),
+    Name(&'static str)
+}
+
+impl From<usize> for Clip {
+    fn from(i: usize) -> Self {
+        Clip::Index(i)
+    }
+}
+
+impl From<&'static str> for Clip {
+    fn from(name: &'static str) -> Self {
+        Clip::Name(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum ClipError {
+    UnknownName(&'static str, Vec<String>),
+    OutOfRange(usize, usize),
+    UnknownBone(&'static str),
+    NotLoaded(usize)
+}
+
+impl std::fmt::Display for ClipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClipError::UnknownName(name, known) => write!(f, "no clip named `{}`, known clips: {}", name, known.join(", ")),
+            ClipError::OutOfRange(i, count) => write!(f, "clip {} requested, but there are only {}", i, count),
+            ClipError::UnknownBone(name) => write!(f, "no bone named `{}` to mask a layer with", name),
+            ClipError::NotLoaded(i) => write!(f, "clip {} or its graph is not loaded", i)
+        }
+    }
+}
+
+impl std::error::Error for ClipError {}
+
+// filled once the glTF is loaded, the graph holds every clip the file has,
+// `expected` is the clip count the code relies on, a different count is reported,
+// `blends` are crossfades for single transitions, `masked` the layer clips built so far
 
 pub struct AnimationSet {
     pub animations: Vec<AnimationNodeIndex>,
     pub graph: Handle<AnimationGraph>,
+    pub gltf: Handle<Gltf>,
+    pub names: Option<HashMap<String, usize>>,
+    pub expected: Option<usize>,
+    pub blends: Vec<(Clip, Clip, Duration)>,
+    pub locomotion: Option<Locomotion>,
+    pub markers: Vec<(Clip, f32, &'static str)>,
+    pub variety: Option<Variety>,
+    pub idles: Vec<(Clip, f32)>,
+    pub aliases: Vec<(&'static str, usize)>,
+    masked: HashMap<(usize, &'static str), AnimationNodeIndex>
+}
+
+impl AnimationSet {
+    pub fn is_loaded(&self) -> bool {
+        self.names.is_some()
+    }
+
+    pub fn index(&self, clip: Clip) -> Result<usize, ClipError> {
+        match clip {
+            Clip::Index(i) if i < self.animations.len() => Ok(i),
+            Clip::Index(i) => Err(ClipError::OutOfRange(i, self.animations.len())),
+            Clip::Name(name) => {
+                let names = self.names.as_ref();
+                names.and_then(|n| n.get(name)).copied().ok_or_else(|| {
+                    let mut known: Vec<String> = names.map(|n| n.keys().cloned().collect()).unwrap_or_default();
+                    known.sort();
+                    ClipError::UnknownName(name, known)
+                })
+            }
+        }
+    }
+
+    fn blend(&self, from: usize, to: usize) -> Option<Duration> {
+        self.blends.iter()
+        .find(|(f, t, _)| self.index(*f).is_ok_and(|f| f == from) && self.index(*t).is_ok_and(|t| t == to))
+        .map(|(_, _, d)| *d)
+    }
+
+    // the clip a graph node plays, masked layer clips count as the clip they were cut from
+
+    fn clip_of(&self, node: AnimationNodeIndex) -> Option<usize> {
+        self.animations.iter().position(|a| *a == node)
+            .or_else(|| self.masked.iter().find(|(_, n)| **n == node).map(|((idx, _), _)| *idx))
+    }
 }
 
 #[derive(Component, PartialEq, Eq, Hash, Debug)]
@@ -24,18 +263,83 @@ pub enum AnimationKey {
 #[derive(Resource)]
 pub struct AllAnimations(pub HashMap<AnimationKey, AnimationSet>);
 impl AllAnimations {
-    pub fn add(&mut self, key: AnimationKey, path: &'static str, count: usize, graphs: &mut ResMut<Assets<AnimationGraph>>, assets: &ResMut<AssetServer>) {
-        let mut graph = AnimationGraph::new();
+    pub fn add(&mut self, key: AnimationKey, path: &'static str, expected: Option<usize>, assets: &ResMut<AssetServer>) {
         self.0.insert(
             key, 
             AnimationSet {
-                animations: graph
-                    .add_clips((0..count).map(|i| {assets.load(GltfAssetLabel::Animation(i).from_asset(path))}), 1.0,graph.root)
-                    .collect(),
-                graph: graphs.add(graph),
+                animations: Vec::new(),
+                graph: Handle::default(),
+                gltf: assets.load(path),
+                names: None,
+                expected,
+                blends: Vec::new(),
+                locomotion: None,
+                markers: Vec::new(),
+                variety: None,
+                idles: Vec::new(),
+                aliases: Vec::new(),
+                masked: HashMap::new()
             }
         );
     }
+
+    // names for the clips of a glTF that has none, checked against the clip count once it is loaded
+
+    pub fn alias(&mut self, key: AnimationKey, aliases: &[(&'static str, usize)]) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.aliases.extend_from_slice(aliases);
+        }
+    }
+
+    // crossfade used when `key` switches from `from` to `to`, other pairs keep DEFAULT_BLEND
+
+    pub fn set_blend(&mut self, key: AnimationKey, from: impl Into<Clip>, to: impl Into<Clip>, blend: Duration) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.blends.push((from.into(), to.into(), blend));
+        }
+    }
+
+    pub fn set_locomotion(&mut self, key: AnimationKey, locomotion: Locomotion) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.locomotion = Some(locomotion);
+        }
+    }
+
+    // keeps a crowd of the same key out of lockstep
+
+    pub fn set_variety(&mut self, key: AnimationKey, variety: Variety) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.variety = Some(variety);
+        }
+    }
+
+    // idle clips with their weights, see `pick_idle`
+
+    pub fn set_idles(&mut self, key: AnimationKey, idles: &[(Clip, f32)]) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.idles = idles.to_vec();
+        }
+    }
+
+    pub fn pick_idle(&self, key: &AnimationKey, rng: &mut fastrand::Rng) -> Option<Clip> {
+        let idles = &self.0.get(key)?.idles;
+        let mut roll = rng.f32() * idles.iter().map(|(_, w)| w).sum::<f32>();
+        idles.iter()
+            .find(|(_, w)| {
+                roll -= w;
+                roll < 0.
+            })
+            .or(idles.last())
+            .map(|(clip, _)| *clip)
+    }
+
+    // AnimationMarker `name` is sent whenever playback of `clip` passes `time` (seconds into the clip)
+
+    pub fn add_marker(&mut self, key: AnimationKey, clip: impl Into<Clip>, time: f32, name: &'static str) {
+        if let Some(ani_set) = self.0.get_mut(&key) {
+            ani_set.markers.push((clip.into(), time, name));
+        }
+    }
 }
 
 #[derive(Component)]
@@ -49,8 +353,11 @@ impl Plugin for AnimatorPlugin {
         app
         .add_systems(Startup, startup)
         .add_systems(Update, setup.run_if(in_state(GameState::Loading)))
+        .add_systems(Update, load_sets.run_if(in_state(GameState::Loading)))
         .add_systems(Update, check.run_if(in_state(GameState::Loading)))
-        .add_systems(Update, switch)
+        .add_systems(Update, (switch, desync, advance_layers, gait, locomotion, finish_once, markers).chain())
+        .add_event::<AnimationFinished>()
+        .add_event::<AnimationMarker>()
         .insert_resource(AllAnimations(HashMap::new()))
         ;
     }
@@ -71,19 +378,20 @@ pub fn setup(
     all_animations: Res<AllAnimations>,
     mut players: Query<(Entity, &mut AnimationPlayer)>,
     objects_q: Query<(Entity, &AnimationKey), (Without<CurrentAnimation>, With<AnimationKey>)>, 
-    children_q : Query<&Children>
+    children_q : Query<&Children>,
+    seed: Res<ShowSeed>
 ) {
     if objects_q.is_empty() {
         return;
     }
 
     for (o_entity, o_akey) in objects_q.iter() {
-        
+        // not loaded yet, or without clips, the others are set up all the same
+        let Some(ani_set) =  all_animations.0.get(o_akey).filter(|s| s.is_loaded() && !s.animations.is_empty())  else {
+            continue;
+        };
         for c  in children_q.iter_descendants(o_entity) {
             if let Ok((entity, mut player)) = players.get_mut(c)  {
-                let Some(ani_set) =  all_animations.0.get(o_akey)  else {
-                    return;
-                };
                 let last_animation = ani_set.animations.len() - 1;
                 let mut transitions = AnimationTransitions::new();
                 transitions
@@ -94,8 +402,14 @@ pub fn setup(
                     .entity(entity)
                     .insert(ani_set.graph.clone())
                     .insert(transitions)
+                    .insert(Layers::default())
                 ;
-                commands.entity(o_entity).insert(CurrentAnimation(last_animation, entity));
+                commands.entity(o_entity).insert((
+                    CurrentAnimation(Clip::Index(last_animation), entity, Playback::default()),
+                    Gait::default(),
+                    MarkerClock::default(),
+                    Desync::new(ani_set.variety, entity_rng(*seed, o_entity))
+                ));
             }
         }
     }
@@ -105,34 +419,735 @@ pub fn setup(
 // ---
 
 pub fn switch(
-    mut animation_players: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
-    objects_q: Query<(&CurrentAnimation, &AnimationKey), Changed<CurrentAnimation>>,
+    mut animation_players: Query<(&mut AnimationPlayer, &mut AnimationTransitions, &mut Layers)>,
+    mut objects_q: Query<(&mut CurrentAnimation, &AnimationKey), Changed<CurrentAnimation>>,
+    mut all_animations: ResMut<AllAnimations>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    children_q: Query<&Children>,
+    bones_q: Query<(&Name, Option<&AnimationTarget>)>
+) {
+    for (mut ca, akey) in objects_q.iter_mut() {
+        let Ok((mut player, mut transitions, mut layers)) = animation_players.get_mut(ca.1) else {
+            continue;
+        };
+        let ani_set = all_animations.0.get_mut(akey).unwrap();
+        let pb = &mut ca.bypass_change_detection().2;
+        let requested_blend = pb.blend.take();
+        let once = pb.once;
+
+        match ani_set.index(ca.0) {
+            Ok(idx) => {
+                let node = ani_set.animations[idx];
+                let main = transitions.get_main_animation();
+                let starting = once.is_some_and(|o| o.node.is_none());
+                // with the one-shot still on only the layer has changed
+                let running = !starting && once.is_some_and(|o| o.node == Some(node));
+                if !running {
+                    let repeat = if starting { RepeatAnimation::Never } else { RepeatAnimation::Forever };
+                    if main != Some(node) {
+                        let from = main.and_then(|m| ani_set.animations.iter().position(|a| *a == m));
+                        let blend = requested_blend
+                            .or_else(|| from.and_then(|from| ani_set.blend(from, idx)))
+                            .unwrap_or(DEFAULT_BLEND);
+                        // a walk / run partner keeps its blend weight when it is promoted
+                        transitions.play(&mut player, node, blend).set_repeat(repeat).set_weight(1.0);
+                    } else if let Some(a) = player.animation_mut(node) {
+                        // the same clip again, a one-shot starts over, a loop just keeps going
+                        if starting {
+                            a.replay();
+                        }
+                        a.set_repeat(repeat);
+                    }
+                    ca.bypass_change_detection().2.once = once
+                        .filter(|_| starting)
+                        .map(|o| OneShot {node: Some(node), ..o});
+                }
+            },
+            Err(e) => error!("{:?}: {}", akey, e)
+        }
+
+        let wanted = match ca.2.layer {
+            Some(layer) => match masked_clip(ani_set, layer, ca.1, &mut graphs, &mut clips, &children_q, &bones_q) {
+                Ok(node) => Some(node),
+                Err(e) => {
+                    error!("{:?}: {}", akey, e);
+                    None
+                }
+            },
+            None => None
+        };
+        if layers.active.map(|(node, _)| node) != wanted {
+            if let Some(old) = layers.active.take() {
+                layers.fading.push(old);
+            }
+            if let Some(node) = wanted {
+                let share = layers.fading.iter().position(|(n, _)| *n == node).map_or(0., |i| layers.fading.remove(i).1);
+                player.start(node).repeat();
+                layers.active = Some((node, share));
+            }
+        }
+    }
+}
+
+// ---
+
+fn finish_once(
+    mut objects_q: Query<(Entity, &mut CurrentAnimation)>,
+    animation_players: Query<&AnimationPlayer>,
+    mut finished_w: EventWriter<AnimationFinished>
+) {
+    for (entity, mut ca) in objects_q.iter_mut() {
+        let Some(OneShot {node: Some(node), back}) = ca.2.once else {
+            continue;
+        };
+        let Ok(player) = animation_players.get(ca.1) else {
+            continue;
+        };
+        if player.animation(node).is_some_and(|a| !a.is_finished()) {
+            continue;
+        }
+        finished_w.send(AnimationFinished(entity, ca.0));
+        ca.bypass_change_detection().2.once = None;
+        if let Some(back) = back {
+            ca.0 = back;
+        }
+    }
+}
+
+// ---
+
+// the positions are compared between frames, so a marker is passed once
+// however fast the clip plays, and again on every loop
+
+fn markers(
+    mut objects_q: Query<(Entity, &CurrentAnimation, &AnimationKey, &mut MarkerClock)>,
+    animation_players: Query<&AnimationPlayer>,
+    all_animations: Res<AllAnimations>,
+    mut marker_w: EventWriter<AnimationMarker>
+) {
+    for (entity, ca, akey, mut clock) in objects_q.iter_mut() {
+        let Some(ani_set) = all_animations.0.get(akey).filter(|s| !s.markers.is_empty()) else {
+            continue;
+        };
+        let Ok(player) = animation_players.get(ca.1) else {
+            continue;
+        };
+        let mut seen = HashMap::new();
+        for (node, a) in player.playing_animations() {
+            let now = (a.elapsed(), a.seek_time(), a.completions());
+            seen.insert(*node, now);
+            if a.weight() < MARKER_MIN_WEIGHT {
+                continue;
+            }
+            let Some(idx) = ani_set.clip_of(*node) else {
+                continue;
+            };
+            let prev = clock.0.get(node).copied();
+            for (clip, time, name) in ani_set.markers.iter() {
+                if ani_set.index(*clip).is_ok_and(|i| i == idx) && passed(prev, now, *time) {
+                    marker_w.send(AnimationMarker(entity, name));
+                }
+            }
+        }
+        clock.0 = seen;
+    }
+}
+
+// positions are (elapsed, seek time, completions), `time` is in seconds into the clip
+
+fn passed(prev: Option<(f32, f32, u32)>, now: (f32, f32, u32), time: f32) -> bool {
+    match prev {
+        // started over
+        Some(prev) if now.0 < prev.0 => time <= now.1,
+        // looped
+        Some(prev) if now.2 > prev.2 => time > prev.1 || time <= now.1,
+        Some(prev) => prev.1 < time && time <= now.1,
+        None => time <= now.1
+    }
+}
+
+// ---
+
+// the layer clip is the requested clip cut down to the bones under the mask,
+// built once per key, clip and mask, the skeleton being the same for the key
+
+fn masked_clip(
+    ani_set: &mut AnimationSet,
+    layer: Layer,
+    player_entity: Entity,
+    graphs: &mut Assets<AnimationGraph>,
+    clips: &mut Assets<AnimationClip>,
+    children_q: &Query<&Children>,
+    bones_q: &Query<(&Name, Option<&AnimationTarget>)>
+) -> Result<AnimationNodeIndex, ClipError> {
+    let idx = ani_set.index(layer.clip)?;
+    if let Some(node) = ani_set.masked.get(&(idx, layer.mask)) {
+        return Ok(*node);
+    }
+
+    let ids = mask_targets(player_entity, layer.mask, children_q, bones_q)?;
+    let graph = graphs.get_mut(&ani_set.graph).ok_or(ClipError::NotLoaded(idx))?;
+    let masked = graph.get(ani_set.animations[idx])
+        .and_then(|n| n.clip.as_ref())
+        .and_then(|h| clips.get(h))
+        .map(|source| mask_clip(source, &ids))
+        .ok_or(ClipError::NotLoaded(idx))?;
+    let node = graph.add_clip(clips.add(masked), 1.0, graph.root);
+    ani_set.masked.insert((idx, layer.mask), node);
+    Ok(node)
+}
+
+// ---
+
+// applied whenever a new main clip starts, one-shots start from their beginning,
+// the marker clock learns where the clip was put, so the skipped part fires no markers
+
+fn desync(
+    mut objects_q: Query<(&CurrentAnimation, &AnimationKey, &mut Desync, &mut MarkerClock)>,
+    mut animation_players: Query<(&mut AnimationPlayer, &AnimationTransitions)>,
     all_animations: Res<AllAnimations>,
+    graphs: Res<Assets<AnimationGraph>>,
+    clips: Res<Assets<AnimationClip>>
+) {
+    for (ca, akey, mut desync, mut clock) in objects_q.iter_mut() {
+        let Ok((mut player, transitions)) = animation_players.get_mut(ca.1) else {
+            continue;
+        };
+        let main = transitions.get_main_animation();
+        if main == desync.main {
+            continue;
+        }
+        desync.main = main;
+        let (Some(node), Some(variety)) = (main, all_animations.0.get(akey).and_then(|s| s.variety)) else {
+            continue;
+        };
+        let duration = all_animations.0.get(akey)
+            .and_then(|s| graphs.get(&s.graph))
+            .and_then(|g| g.get(node))
+            .and_then(|n| n.clip.as_ref())
+            .and_then(|h| clips.get(h))
+            .map_or(0., |c| c.duration());
+        let Some(a) = player.animation_mut(node) else {
+            continue;
+        };
+        a.set_speed(desync.rate);
+        if variety.random_phase && a.repeat_mode() == RepeatAnimation::Forever {
+            let phase = desync.rng.f32() * duration;
+            a.seek_to(phase);
+            clock.0.insert(node, (a.elapsed(), phase, a.completions()));
+        }
+    }
+}
+
+// the animation targets of the bone named `mask` and everything under it
+
+fn mask_targets(
+    player_entity: Entity,
+    mask: &'static str,
+    children_q: &Query<&Children>,
+    bones_q: &Query<(&Name, Option<&AnimationTarget>)>
+) -> Result<HashSet<AnimationTargetId>, ClipError> {
+    let root = children_q.iter_descendants(player_entity)
+        .find(|e| bones_q.get(*e).is_ok_and(|(name, _)| name.as_str() == mask))
+        .ok_or(ClipError::UnknownBone(mask))?;
+    Ok(
+        std::iter::once(root)
+        .chain(children_q.iter_descendants(root))
+        .filter_map(|e| bones_q.get(e).ok().and_then(|(_, target)| target.map(|t| t.id)))
+        .collect()
+    )
+}
+
+fn mask_clip(source: &AnimationClip, ids: &HashSet<AnimationTargetId>) -> AnimationClip {
+    let mut masked = AnimationClip::default();
+    for (id, curves) in source.curves().iter().filter(|(id, _)| ids.contains(*id)) {
+        for curve in curves {
+            masked.add_curve_to_target(*id, curve.clone());
+        }
+    }
+    masked
+}
+
+// ---
+
+// clips are blended by weight / total weight, so a share `s` of the bones
+// is a weight of s / (1 - s) next to the main clip
+
+fn advance_layers(
+    mut players_q: Query<(&mut AnimationPlayer, &mut Layers)>,
+    time: Res<Time>
+) {
+    let step = time.delta_seconds() / LAYER_FADE;
+    let weight = |share: f32| share / (1. - share);
+    for (mut player, mut layers) in players_q.iter_mut() {
+        let layers = &mut *layers;
+        if let Some((node, share)) = layers.active.as_mut() {
+            *share = (*share + step).min(MAX_LAYER_SHARE);
+            if let Some(a) = player.animation_mut(*node) {
+                a.set_weight(weight(*share));
+            }
+        }
+        layers.fading.retain_mut(|(node, share)| {
+            *share -= step;
+            if *share <= 0. {
+                player.stop(*node);
+                return false;
+            }
+            if let Some(a) = player.animation_mut(*node) {
+                a.set_weight(weight(*share));
+            }
+            true
+        });
+    }
+}
+
+// ---
+
+// the speed is the one the path is walked at, not measured from the transforms,
+// those move in fixed steps that don't match the frames in deterministic mode.
+// It is taken on the ground plane, so a lift ride doesn't make anybody march
+
+fn gait(
+    mut objects_q: Query<(&Transform, &mut Gait, Option<&MovementPath>, Has<MovementPaused>)>,
+    time: Res<Time>
+) {
+    let dt = time.delta_seconds();
+    for (t, mut gait, mp, paused) in objects_q.iter_mut() {
+        let speed = mp.filter(|_| !paused).map_or(0., |mp| mp.ground_speed(t.translation));
+        gait.speed += (speed - gait.speed) * (dt * SPEED_SMOOTHING).min(1.);
+    }
+}
+
+// walk and run clips play at the speed the character moves
+
+fn locomotion(
+    mut objects_q: Query<(&CurrentAnimation, &AnimationKey, &mut Gait, Option<&Desync>)>,
+    mut animation_players: Query<(&mut AnimationPlayer, &AnimationTransitions)>,
+    all_animations: Res<AllAnimations>
 ) {
-    for (ca, akey) in objects_q.iter() {
-        if let Ok((mut player, mut transitions)) = animation_players.get_mut(ca.1) {
-            let ani_set = all_animations.0.get(akey).unwrap();
-            transitions
-            .play(
-                &mut player,
-                ani_set.animations[ca.0],
-                Duration::from_millis(250),
-            )
-            .repeat();            
+    for (ca, akey, mut gait, desync) in objects_q.iter_mut() {
+        let Some(ani_set) = all_animations.0.get(akey) else {
+            continue;
+        };
+        let Ok((mut player, transitions)) = animation_players.get_mut(ca.1) else {
+            continue;
+        };
+        let main = transitions.get_main_animation();
+        let gaits = ani_set.locomotion.and_then(|l| {
+            let walk = ani_set.animations[ani_set.index(l.walk).ok()?];
+            let run = ani_set.animations[ani_set.index(l.run).ok()?];
+            Some((l, walk, run))
+        });
+
+        // (main clip, its stride speed, other clip, its stride speed, share of the other one)
+        let blend = gaits.and_then(|(l, walk, run)| {
+            let t = ((gait.speed - l.walk_speed) / (l.run_speed - l.walk_speed)).clamp(0., 1.);
+            if main == Some(walk) {
+                Some((walk, l.walk_speed, run, l.run_speed, t))
+            } else if main == Some(run) {
+                Some((run, l.run_speed, walk, l.walk_speed, 1. - t))
+            } else {
+                None
+            }
+        });
+
+        let partner = blend.filter(|b| b.4 > MIN_GAIT_SHARE).map(|b| b.2);
+        if gait.partner != partner {
+            if let Some(old) = gait.partner.filter(|old| Some(*old) != main) {
+                player.stop(old);
+            }
+            gait.partner = partner;
+        }
+
+        let Some((main, main_stride, other, other_stride, share)) = blend else {
+            continue;
+        };
+        let own = desync.map_or(1., |d| d.rate);
+        let rate = |stride: f32| (gait.speed / stride).clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED) * own;
+        if let Some(a) = player.animation_mut(main) {
+            a.set_speed(rate(main_stride));
+        }
+        if partner.is_some() {
+            // the transitions may have stopped it while it was fading out as the main clip
+            if player.animation(other).is_none() {
+                player.play(other).repeat();
+            }
+            if let Some(a) = player.animation_mut(other) {
+                let share = share.min(MAX_LAYER_SHARE);
+                a.set_speed(rate(other_stride)).set_weight(share / (1. - share));
+            }
         }
     }
 }
 
 // ---
 
+// the graph is built from the clips the glTF actually has,
+// clip names come from the glTF too, mapped onto the clip positions,
+// a glTF that failed to load ends up as a set without clips, so that loading goes on
+
+fn load_sets(
+    mut all_animations: ResMut<AllAnimations>,
+    gltfs: Res<Assets<Gltf>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    assets: Res<AssetServer>
+) {
+    for (akey, ani_set) in all_animations.0.iter_mut().filter(|(_, s)| !s.is_loaded()) {
+        if let Some(LoadState::Failed(e)) = assets.get_load_state(&ani_set.gltf) {
+            error!("{:?}: the glTF failed to load: {}", akey, e);
+            ani_set.names = Some(HashMap::new());
+            continue;
+        }
+        let Some(gltf) = gltfs.get(&ani_set.gltf) else {
+            continue;
+        };
+        let count = gltf.animations.len();
+        match ani_set.expected {
+            Some(expected) if expected != count => warn!("{:?}: {} clips expected, the glTF has {}", akey, expected, count),
+            _ if count == 0 => warn!("{:?}: the glTF has no clips", akey),
+            _ => {}
+        }
+        let mut graph = AnimationGraph::new();
+        ani_set.animations = graph.add_clips(gltf.animations.iter().cloned(), 1.0, graph.root).collect();
+        ani_set.graph = graphs.add(graph);
+        let named = gltf.named_animations.iter()
+            .filter_map(|(name, h)| gltf.animations.iter().position(|a| a == h).map(|i| (name.to_string(), i)));
+        let (names, errors) = clip_names(named, &ani_set.aliases, count);
+        for e in errors {
+            error!("{:?}: {}", akey, e);
+        }
+        ani_set.names = Some(names);
+    }
+}
+
+// the glTF names and the registered aliases, an alias past the last clip is reported and left out
+
+fn clip_names(
+    named: impl Iterator<Item = (String, usize)>,
+    aliases: &[(&'static str, usize)],
+    count: usize
+) -> (HashMap<String, usize>, Vec<ClipError>) {
+    let mut names: HashMap<String, usize> = named.collect();
+    let mut errors = Vec::new();
+    for (name, i) in aliases {
+        if *i < count {
+            names.insert(name.to_string(), *i);
+        } else {
+            errors.push(ClipError::OutOfRange(*i, count));
+        }
+    }
+    (names, errors)
+}
+
+// ---
+
+// objects whose set has no clips are never set up, they don't hold loading back
+
 pub fn check(
     animated_q: Query<&AnimationKey, Without<CurrentAnimation>>,
     check_q: Query<Entity, (With<NotReady>, With<TempAnimatorMarker>)>,
+    all_animations: Res<AllAnimations>,
     mut cmd: Commands
 ) {
-    if animated_q.is_empty() {
+    let waiting = animated_q.iter().any(|akey| !all_animations.0.get(akey).is_some_and(|s| s.animations.is_empty()));
+    if !waiting && all_animations.0.values().all(|s| s.is_loaded()) {
         if let Ok(check_e) = check_q.get_single() {
             cmd.entity(check_e).despawn_recursive();
         }
     }
 }
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::animation::{Interpolation, Keyframes, VariableCurve};
+    use bevy::ecs::system::{RunSystemOnce, SystemState};
+
+    fn curve() -> VariableCurve {
+        VariableCurve {
+            keyframe_timestamps: vec![0., 1.],
+            keyframes: Keyframes::Translation(vec![Vec3::ZERO, Vec3::X]),
+            interpolation: Interpolation::Linear
+        }
+    }
+
+    // player -> hips -> (spine -> arm, leg)
+
+    fn skeleton(world: &mut World) -> (Entity, [AnimationTargetId; 4]) {
+        let player = world.spawn(Name::new("player")).id();
+        let names = ["hips", "spine", "arm", "leg"];
+        let ids = names.map(|n| AnimationTargetId::from_name(&Name::new(n)));
+        let bones: Vec<Entity> = names.iter().zip(ids)
+            .map(|(n, id)| world.spawn((Name::new(*n), AnimationTarget {id, player})).id())
+            .collect();
+        world.entity_mut(player).push_children(&[bones[0]]);
+        world.entity_mut(bones[0]).push_children(&[bones[1], bones[3]]);
+        world.entity_mut(bones[1]).push_children(&[bones[2]]);
+        (player, ids)
+    }
+
+    #[test]
+    fn marker_on_normal_advance() {
+        let prev = Some((1.0, 0.4, 0));
+        assert!(passed(prev, (1.1, 0.5, 0), 0.45));
+        assert!(passed(prev, (1.1, 0.5, 0), 0.5));
+        assert!(!passed(prev, (1.1, 0.5, 0), 0.4));
+        assert!(!passed(prev, (1.1, 0.5, 0), 0.7));
+        // a fast clip still passes it once
+        assert!(passed(prev, (1.5, 0.9, 0), 0.6));
+        assert!(!passed(Some((1.5, 0.9, 0)), (1.6, 1.0, 0), 0.6));
+    }
+
+    #[test]
+    fn marker_on_wrap_around() {
+        // a 1 s clip going from 0.9 over the end to 0.1
+        let prev = Some((2.9, 0.9, 2));
+        let now = (3.1, 0.1, 3);
+        assert!(passed(prev, now, 0.95));
+        assert!(passed(prev, now, 0.05));
+        assert!(!passed(prev, now, 0.5));
+    }
+
+    #[test]
+    fn marker_on_restart() {
+        let prev = Some((5.0, 0.8, 4));
+        let now = (0.1, 0.1, 0);
+        assert!(passed(prev, now, 0.));
+        assert!(passed(prev, now, 0.05));
+        assert!(!passed(prev, now, 0.9));
+        assert!(passed(None, (0.1, 0.1, 0), 0.05));
+    }
+
+    #[test]
+    fn marker_after_a_random_phase() {
+        // desync puts a restarted clip at 0.7 and records it, the skipped part stays quiet
+        let seeked = Some((0., 0.7, 0));
+        assert!(!passed(seeked, (0., 0.7, 0), 0.3));
+        assert!(!passed(seeked, (0., 0.7, 0), 0.7));
+        assert!(passed(seeked, (0.1, 0.8, 0), 0.75));
+    }
+
+    #[test]
+    fn mask_takes_the_bone_and_everything_under_it() {
+        let mut world = World::new();
+        let (player, [hips, spine, arm, leg]) = skeleton(&mut world);
+        let mut children: SystemState<Query<&Children>> = SystemState::new(&mut world);
+        let mut bones: SystemState<Query<(&Name, Option<&AnimationTarget>)>> = SystemState::new(&mut world);
+        let (children_q, bones_q) = (children.get(&world), bones.get(&world));
+
+        let ids = mask_targets(player, "spine", &children_q, &bones_q).unwrap();
+        assert_eq!(ids, HashSet::from_iter([spine, arm]));
+        assert!(!ids.contains(&hips) && !ids.contains(&leg));
+        assert!(matches!(mask_targets(player, "tail", &children_q, &bones_q), Err(ClipError::UnknownBone("tail"))));
+    }
+
+    #[test]
+    fn masked_clip_keeps_only_masked_curves() {
+        let ids = ["hips", "spine", "arm", "leg"].map(|n| AnimationTargetId::from_name(&Name::new(n)));
+        let mut source = AnimationClip::default();
+        for id in ids {
+            source.add_curve_to_target(id, curve());
+        }
+        let masked = mask_clip(&source, &HashSet::from_iter([ids[1], ids[2]]));
+        assert_eq!(masked.curves().len(), 2);
+        assert!(masked.curves_for_target(ids[1]).is_some());
+        assert!(masked.curves_for_target(ids[2]).is_some());
+        assert!(masked.curves_for_target(ids[0]).is_none());
+        assert_eq!(masked.duration(), source.duration());
+    }
+
+    #[test]
+    fn layers_fade_in_and_out() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let node = AnimationNodeIndex::new(1);
+        let mut player = AnimationPlayer::default();
+        player.start(node);
+        let e = world.spawn((player, Layers {active: Some((node, 0.)), fading: Vec::new()})).id();
+
+        let step = |world: &mut World, secs: f32| {
+            world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+            world.run_system_once(advance_layers);
+        };
+
+        // half way in, the layer weighs as much as the main clip
+        step(&mut world, LAYER_FADE / 2.);
+        let weight = world.get::<AnimationPlayer>(e).unwrap().animation(node).unwrap().weight();
+        assert!((weight - 1.).abs() < 1e-4);
+
+        // fully in, it takes over the masked bones but never divides by zero
+        step(&mut world, LAYER_FADE);
+        let weight = world.get::<AnimationPlayer>(e).unwrap().animation(node).unwrap().weight();
+        assert!(weight.is_finite() && weight > 50.);
+
+        // out again, the clip is stopped once its share is gone
+        let mut layers = world.get_mut::<Layers>(e).unwrap();
+        let active = layers.active.take().unwrap();
+        layers.fading.push(active);
+        step(&mut world, LAYER_FADE * 1.1);
+        assert!(world.get::<AnimationPlayer>(e).unwrap().animation(node).is_none());
+        assert!(world.get::<Layers>(e).unwrap().fading.is_empty());
+    }
+
+    fn set(animations: Vec<AnimationNodeIndex>, graph: Handle<AnimationGraph>) -> AnimationSet {
+        AnimationSet {
+            animations,
+            graph,
+            gltf: Handle::default(),
+            names: Some(HashMap::new()),
+            expected: None,
+            blends: Vec::new(),
+            locomotion: None,
+            markers: Vec::new(),
+            variety: None,
+            idles: Vec::new(),
+            aliases: Vec::new(),
+            masked: HashMap::new()
+        }
+    }
+
+    #[test]
+    fn aliases_name_clips_within_range() {
+        let named = [("wave".to_string(), 0)].into_iter();
+        let (names, errors) = clip_names(named, &[("run", 1), ("fly", 12), ("walk", 2)], 3);
+        assert_eq!(names.get("wave"), Some(&0));
+        assert_eq!(names.get("run"), Some(&1));
+        assert_eq!(names.get("walk"), Some(&2));
+        assert!(!names.contains_key("fly"));
+        assert!(matches!(errors[..], [ClipError::OutOfRange(12, 3)]));
+
+        let mut ani_set = set(vec![AnimationNodeIndex::new(1); 3], Handle::default());
+        ani_set.names = Some(names);
+        assert_eq!(ani_set.index(Clip::Name("run")).unwrap(), 1);
+        assert!(matches!(ani_set.index(Clip::Name("fly")), Err(ClipError::UnknownName("fly", _))));
+    }
+
+    #[test]
+    fn sets_up_the_loaded_while_others_load() {
+        let mut world = World::new();
+        world.insert_resource(ShowSeed(0));
+        let mut all = AllAnimations(HashMap::new());
+        let mut loading = set(Vec::new(), Handle::default());
+        loading.names = None;
+        all.0.insert(AnimationKey::Girl, loading);
+        all.0.insert(AnimationKey::Man, set(vec![AnimationNodeIndex::new(1)], Handle::default()));
+        world.insert_resource(all);
+
+        let objects = [AnimationKey::Girl, AnimationKey::Man].map(|key| {
+            let player = world.spawn(AnimationPlayer::default()).id();
+            let object = world.spawn(key).id();
+            world.entity_mut(object).push_children(&[player]);
+            object
+        });
+        world.run_system_once(setup);
+        assert!(world.get::<CurrentAnimation>(objects[0]).is_none());
+        assert!(world.get::<CurrentAnimation>(objects[1]).is_some());
+    }
+
+    #[test]
+    fn layer_plays_on_the_masked_bones() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let (player, ids) = skeleton(&mut world);
+
+        let mut clips = Assets::<AnimationClip>::default();
+        let mut graphs = Assets::<AnimationGraph>::default();
+        let sources = [0, 1].map(|_| {
+            let mut clip = AnimationClip::default();
+            for id in ids {
+                clip.add_curve_to_target(id, curve());
+            }
+            clips.add(clip)
+        });
+        let mut graph = AnimationGraph::new();
+        let nodes: Vec<AnimationNodeIndex> = graph.add_clips(sources, 1.0, graph.root).collect();
+        let mut all = AllAnimations(HashMap::new());
+        all.0.insert(AnimationKey::Girl, set(nodes.clone(), graphs.add(graph)));
+        world.insert_resource(all);
+        world.insert_resource(clips);
+        world.insert_resource(graphs);
+
+        let mut animation_player = AnimationPlayer::default();
+        let mut transitions = AnimationTransitions::new();
+        transitions.play(&mut animation_player, nodes[0], Duration::ZERO).repeat();
+        world.entity_mut(player).insert((animation_player, transitions, Layers::default()));
+        let object = world.spawn((CurrentAnimation(Clip::Index(0), player, Playback::default()), AnimationKey::Girl)).id();
+
+        let step = |world: &mut World, secs: f32| {
+            world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+            world.run_system_once(switch);
+            world.run_system_once(advance_layers);
+        };
+
+        world.get_mut::<CurrentAnimation>(object).unwrap().set_layer(1, "spine");
+        step(&mut world, LAYER_FADE / 2.);
+
+        // a clip of its own, cut down to spine and arm, as heavy as the main clip half way in
+        let (node, _) = world.get::<Layers>(player).unwrap().active.unwrap();
+        assert!(!nodes.contains(&node));
+        let graphs = world.resource::<Assets<AnimationGraph>>();
+        let all = world.resource::<AllAnimations>();
+        let clip = graphs.get(&all.0[&AnimationKey::Girl].graph).unwrap().get(node).unwrap().clip.clone().unwrap();
+        let clip = world.resource::<Assets<AnimationClip>>().get(&clip).unwrap();
+        assert_eq!(clip.curves().len(), 2);
+        assert!(clip.curves_for_target(ids[1]).is_some() && clip.curves_for_target(ids[2]).is_some());
+        let players = world.get::<AnimationPlayer>(player).unwrap();
+        assert!((players.animation(node).unwrap().weight() - 1.).abs() < 1e-4);
+        assert!(players.animation(nodes[0]).is_some());
+        assert_eq!(world.get::<CurrentAnimation>(object).unwrap().layer(), Some(Layer {clip: Clip::Index(1), mask: "spine"}));
+
+        // cleared, it fades out and stops, the main clip goes on
+        world.get_mut::<CurrentAnimation>(object).unwrap().clear_layer();
+        step(&mut world, LAYER_FADE);
+        let players = world.get::<AnimationPlayer>(player).unwrap();
+        assert!(players.animation(node).is_none());
+        assert!(players.animation(nodes[0]).is_some());
+    }
+
+    #[test]
+    fn walk_and_run_share_by_speed() {
+        let mut world = World::new();
+        let (walk, run) = (AnimationNodeIndex::new(1), AnimationNodeIndex::new(2));
+        let mut ani_set = set(vec![walk, run], Handle::default());
+        ani_set.locomotion = Some(Locomotion {walk: Clip::Index(0), walk_speed: 2., run: Clip::Index(1), run_speed: 5.});
+        let mut all = AllAnimations(HashMap::new());
+        all.0.insert(AnimationKey::Man, ani_set);
+        world.insert_resource(all);
+
+        let mut animation_player = AnimationPlayer::default();
+        let mut transitions = AnimationTransitions::new();
+        transitions.play(&mut animation_player, walk, Duration::ZERO).repeat();
+        let player = world.spawn((animation_player, transitions)).id();
+        let object = world.spawn((CurrentAnimation(Clip::Index(0), player, Playback::default()), AnimationKey::Man, Gait::default())).id();
+
+        // (speed, walk rate, run rate and weight when it plays)
+        let check = |world: &mut World, speed: f32, walk_rate: f32, run_share: Option<(f32, f32)>| {
+            world.get_mut::<Gait>(object).unwrap().speed = speed;
+            world.run_system_once(locomotion);
+            let players = world.get::<AnimationPlayer>(player).unwrap();
+            assert!((players.animation(walk).unwrap().speed() - walk_rate).abs() < 1e-4, "walk at {speed}");
+            match run_share {
+                Some((rate, weight)) => {
+                    let a = players.animation(run).unwrap();
+                    assert!((a.speed() - rate).abs() < 1e-4 && (a.weight() - weight).abs() < 1e-3, "run at {speed}");
+                },
+                None => assert!(players.animation(run).is_none(), "run at {speed}")
+            }
+        };
+        // at the walk stride the walk alone at its own pace
+        check(&mut world, 2., 1., None);
+        // half way, both as heavy, each at the rate for its stride
+        check(&mut world, 3.5, 1.75, Some((0.7, 1.)));
+        // a quarter run
+        check(&mut world, 2.75, 1.375, Some((0.55, 1. / 3.)));
+        // past the run stride the run takes over, the walk rate is capped
+        check(&mut world, 6., MAX_PLAYBACK_SPEED, Some((1.2, MAX_LAYER_SHARE / (1. - MAX_LAYER_SHARE))));
+        // standing, the partner stops and the walk doesn't freeze
+        check(&mut world, 0., MIN_PLAYBACK_SPEED, None);
+    }
+
+}
+
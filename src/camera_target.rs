@@ -1,11 +1,11 @@
 use bevy::prelude::*;
 
-use crate::camera::Cam;
+use crate::{camera::Cam, clock::ShowUpdate};
 pub struct CameraTargetPlugin;
 impl Plugin for CameraTargetPlugin {
     fn build(&self, app: &mut App) {
         app
-        .add_systems(Update, follow)
+        .add_systems(ShowUpdate, follow)
         .observe(set_target)
         ;
     }
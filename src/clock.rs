@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use bevy::{ecs::{schedule::{ExecutorKind, ScheduleLabel}, system::SystemParam}, prelude::*, time::TimeUpdateStrategy};
+use crate::shared::{ShowRng, ShowSeed};
+
+// Choreography systems (movement, camera, props) live in ShowUpdate.
+// Normally it runs every frame, in deterministic mode it steps on the fixed clock, every frame advances
+// time by exactly one step, all randomness comes from the seeded ShowRng and systems run single threaded,
+// so the same seed gives the same transforms frame for frame (the music is not slowed down with it)
+
+pub struct ClockPlugin {
+    pub seed: Option<u64>
+}
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = self.seed.unwrap_or_else(|| fastrand::u64(..));
+        app
+        .init_schedule(ShowUpdate)
+        .insert_resource(ShowSeed(seed))
+        .insert_resource(ShowRng(fastrand::Rng::with_seed(seed)))
+        ;
+        if self.seed.is_some() {
+            app
+            .insert_resource(Deterministic)
+            .insert_resource(Time::<Fixed>::from_hz(SHOW_HZ))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1. / SHOW_HZ)))
+            .add_systems(FixedUpdate, run_show)
+            ;
+        } else {
+            app
+            .add_systems(Update, run_show)
+            ;
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        if self.seed.is_some() {
+            for (_, schedule) in app.world_mut().resource_mut::<Schedules>().iter_mut() {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            }
+        }
+    }
+}
+
+// ---
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShowUpdate;
+
+const SHOW_HZ: f64 = 60.;
+
+#[derive(Resource)]
+pub struct Deterministic;
+
+// the clock ShowUpdate steps on, for systems outside of it that start or schedule show movement
+
+#[derive(SystemParam)]
+pub struct ShowTime<'w> {
+    fixed: Res<'w, Time<Fixed>>,
+    real: Res<'w, Time<Virtual>>,
+    deterministic: Option<Res<'w, Deterministic>>
+}
+
+impl ShowTime<'_> {
+    pub fn now(&self) -> Time {
+        if self.deterministic.is_some() {
+            self.fixed.as_generic()
+        } else {
+            self.real.as_generic()
+        }
+    }
+}
+
+// ---
+
+fn run_show(
+    world: &mut World
+) {
+    world.run_schedule(ShowUpdate);
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        falling::{Falling, FallingPlugin},
+        girls::Girl,
+        men::{Man, Pair},
+        movement::{MovementCommandsExt, MovementPath, MovementPlugin, PathInterpolation},
+        music::MusicTimeline,
+        parachute::{Parachute, ParachutePlugin},
+        shared::random_pos,
+        steering::{Separation, SteeringPlugin}
+    };
+
+    const FRAMES: usize = 600;
+
+    // girls walk random routes through each other, men jump with random pushes and glide to them
+
+    fn stage(
+        mut cmd: Commands,
+        mut rng: ResMut<ShowRng>
+    ) {
+        let girls: Vec<Entity> = (0 .. 16).map(|_| {
+            let start = random_pos(&mut rng.0, Vec3::ZERO, 10.);
+            let mut path = MovementPath::builder();
+            for _ in 0 .. 4 {
+                path = path.to(random_pos(&mut rng.0, Vec3::ZERO, 10.), 1. + rng.0.f32() * 2.);
+            }
+            let interpolation = if rng.0.bool() {PathInterpolation::CatmullRom} else {PathInterpolation::Linear};
+            let e = cmd.spawn((Girl, Transform::from_translation(start), Visibility::Visible, Separation::new(0.35, 1.))).id();
+            cmd.entity(e).start_path(path.interpolation(interpolation).turn_speed(8.));
+            e
+        }).collect();
+
+        for girl in girls.iter().take(8) {
+            let push = Vec3::new(rng.0.f32() - 0.5, 0., rng.0.f32() - 0.5) * 4.;
+            let spin = Vec3::new(rng.0.f32(), rng.0.f32(), rng.0.f32());
+            cmd.spawn((
+                Man,
+                Pair(*girl),
+                Transform::from_translation(random_pos(&mut rng.0, Vec3::Y * 60., 20.)),
+                Visibility::Visible,
+                Separation::new(0.4, 0.5),
+                Falling::new(push, spin),
+                Parachute::new(30. + rng.0.f32() * 10.)
+            ));
+        }
+    }
+
+    // transforms of everyone on stage, frame by frame
+
+    fn run(seed: u64) -> Vec<Vec<(Entity, Transform)>> {
+        let mut app = App::new();
+        app
+        .add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_resource::<MusicTimeline>()
+        .add_plugins((
+            ClockPlugin {seed: Some(seed)},
+            MovementPlugin,
+            SteeringPlugin,
+            FallingPlugin,
+            ParachutePlugin
+        ))
+        .add_systems(Startup, stage)
+        ;
+        (0 .. FRAMES).map(|_| {
+            app.update();
+            let world = app.world_mut();
+            let mut q = world.query_filtered::<(Entity, &Transform), Or<(With<Girl>, With<Man>)>>();
+            let mut transforms: Vec<(Entity, Transform)> = q.iter(world).map(|(e, t)| (e, *t)).collect();
+            transforms.sort_by_key(|(e, _)| *e);
+            transforms
+        }).collect()
+    }
+
+    #[test]
+    fn same_seed_same_show() {
+        let first = run(7);
+        assert!(first.iter().all(|frame| frame.len() == 24));
+        let again = run(7);
+        for (i, (a, b)) in first.iter().zip(again.iter()).enumerate() {
+            assert_eq!(a, b, "frame {i}");
+        }
+        assert_ne!(first.last(), run(8).last());
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::{Cam, ViewportGeom},
+    camera_target::CameraTarget,
+    men::Pair,
+    movement::MovementPath,
+    steering::Separation
+};
+
+// built only with `--features debug_overlay`,
+// F1 shows / hides everything, F2 .. F6 switch single categories
+
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<DebugOverlay>()
+        .add_systems(Update, key_input)
+        .add_systems(Update, (
+            draw_paths.run_if(|o: Res<DebugOverlay>| o.paths),
+            draw_targets.run_if(|o: Res<DebugOverlay>| o.targets),
+            draw_pairs.run_if(|o: Res<DebugOverlay>| o.pairs),
+            draw_cameras.run_if(|o: Res<DebugOverlay>| o.cameras),
+            draw_viewports.run_if(|o: Res<DebugOverlay>| o.viewports),
+        ).run_if(|o: Res<DebugOverlay>| o.enabled))
+        ;
+    }
+}
+
+// ---
+
+#[derive(Resource)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+    pub paths: bool,
+    pub targets: bool,
+    pub pairs: bool,
+    pub cameras: bool,
+    pub viewports: bool
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay {
+            enabled: false,
+            paths: true,
+            targets: true,
+            pairs: true,
+            cameras: true,
+            viewports: true
+        }
+    }
+}
+
+const PATH_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const TARGET_COLOR: Color = Color::srgb(0., 1., 0.);
+const STEERING_COLOR: Color = Color::srgb(1., 0.5, 0.);
+const PAIR_COLOR: Color = Color::srgb(1., 0., 1.);
+const CAMERA_COLOR: Color = Color::srgb(0., 1., 1.);
+const VIEWPORT_COLOR: Color = Color::srgb(1., 1., 0.);
+const MARKER_RADIUS: f32 = 0.2;
+const VIEWPORT_DISTANCE: f32 = 1.;
+
+// ---
+
+fn key_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>
+) {
+    let overlay = &mut *overlay;
+    if keys.just_pressed(KeyCode::F1) {
+        overlay.enabled = !overlay.enabled;
+    }
+    for (key, flag) in [
+        (KeyCode::F2, &mut overlay.paths),
+        (KeyCode::F3, &mut overlay.targets),
+        (KeyCode::F4, &mut overlay.pairs),
+        (KeyCode::F5, &mut overlay.cameras),
+        (KeyCode::F6, &mut overlay.viewports)
+    ] {
+        if keys.just_pressed(key) {
+            *flag = !*flag;
+        }
+    }
+}
+
+// ---
+
+fn draw_paths(
+    paths_q: Query<&MovementPath>,
+    mut gizmos: Gizmos
+) {
+    for mp in &paths_q {
+        gizmos.linestrip(mp.points().iter().map(|p| p.pos), PATH_COLOR);
+    }
+}
+
+// ---
+
+fn draw_targets(
+    paths_q: Query<(&Transform, &MovementPath, Option<&Separation>)>,
+    mut gizmos: Gizmos
+) {
+    for (t, mp, sep) in &paths_q {
+        let target = mp.current().pos;
+        gizmos.line(t.translation, target, TARGET_COLOR);
+        gizmos.sphere(target, Quat::IDENTITY, MARKER_RADIUS, TARGET_COLOR);
+        if let Some(sep) = sep {
+            gizmos.arrow(t.translation, t.translation + sep.steering, STEERING_COLOR);
+        }
+    }
+}
+
+// ---
+
+fn draw_pairs(
+    pairs_q: Query<(&Transform, &Pair)>,
+    t_q: Query<&Transform>,
+    mut gizmos: Gizmos
+) {
+    for (t, pair) in &pairs_q {
+        if let Ok(pt) = t_q.get(pair.0) {
+            gizmos.line(t.translation, pt.translation, PAIR_COLOR);
+        }
+    }
+}
+
+// ---
+
+fn draw_cameras(
+    cams_q: Query<&CameraTarget, With<Cam>>,
+    t_q: Query<&Transform>,
+    mut gizmos: Gizmos
+) {
+    for target in &cams_q {
+        let pos = match (target.entity, target.position) {
+            (Some(e), _) => t_q.get(e).map(|t| t.translation).ok(),
+            (None, p) => p
+        };
+        if let Some(pos) = pos {
+            gizmos.sphere(pos, Quat::IDENTITY, MARKER_RADIUS, CAMERA_COLOR);
+            if let Some(d) = target.direction {
+                gizmos.arrow(pos, pos + *d, CAMERA_COLOR);
+            }
+        }
+    }
+}
+
+// ---
+
+// the outline is drawn just in front of the camera, so it frames its own viewport
+// and shows up as a small frustum in the others
+
+fn draw_viewports(
+    cams_q: Query<(&Camera, &GlobalTransform), With<ViewportGeom>>,
+    mut gizmos: Gizmos
+) {
+    for (camera, gt) in &cams_q {
+        let Some(rect) = camera.logical_viewport_rect() else {
+            continue;
+        };
+        let size = rect.size() - Vec2::ONE;
+        let corners: Vec<Vec3> = [Vec2::ZERO, Vec2::new(size.x, 0.), size, Vec2::new(0., size.y), Vec2::ZERO]
+            .iter()
+            .filter_map(|c| camera.viewport_to_world(gt, *c).map(|ray| ray.get_point(VIEWPORT_DISTANCE)))
+            .collect();
+        gizmos.linestrip(corners, VIEWPORT_COLOR);
+    }
+}
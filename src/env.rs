@@ -4,6 +4,9 @@ use bevy::input::keyboard::KeyboardInput;
 
 use crate::{GameState, NotReady};
 use crate::camera::CameraSplitConf;
+use crate::formation::Formation;
+use crate::ground::Ground;
+use crate::nav::Obstacle;
 pub struct EnvPlugin;
 impl Plugin for EnvPlugin {
     fn build(&self, app: &mut App) {
@@ -50,6 +53,8 @@ pub struct History;
 
 const SEATS_COUNT: u8 = 4;
 const AMBIENT_LIGHT_DEFAULT: f32 = 0.0;
+const BENCH_FOOTPRINT: Vec2 = Vec2::new(2.2, 0.5);
+const LANTERN_FOOTPRINT: Vec2 = Vec2::new(0.3, 0.3);
 
 // ---
 
@@ -59,13 +64,14 @@ fn startup(
     mut al: ResMut<AmbientLight>
 ) {
     al.brightness = AMBIENT_LIGHT_DEFAULT;
-    commands.spawn(
+    commands.spawn((
         SceneBundle {
             scene: aserver.load("models/scenes/scene0.glb#Scene0"),
             transform: Transform::from_xyz(0., 0., 0.),
             ..default()
-        }
-    );
+        },
+        Ground
+    ));
 
     commands.spawn((
         SceneBundle {
@@ -88,26 +94,30 @@ fn startup(
     let bench = aserver.load("models/scenes/bench.glb#Scene0");
     let lantern = aserver.load("models/scenes/lantern.glb#Scene0");
 
+    // two short rows of benches on both sides of every avenue, facing across it
     for i in 0 .. 2 {
         let is_horizontal = i == 0;
         let face_to = if is_horizontal {Vec3::Z} else {Vec3::X};
         for j in 0 .. 2 {
             let sign0 = if j == 0 {1.} else {-1.};
-            let mut from = sign0 * if is_horizontal {Vec3::new(25., 0. ,0.)} else {Vec3::new(0., 0.,25.)};
-            let step = from / 3.;
-            
-            for _k in 0 .. 2 {
-                from += step;
-                for l in  0 .. 2 {
-                    let sign1 = if l == 0 {1.} else {-1.};
-                    let bench_pos = from  + face_to * sign1 * 8.;
+            let along = sign0 * if is_horizontal {Vec3::X} else {Vec3::Z};
+            for l in  0 .. 2 {
+                let sign1 = if l == 0 {1.} else {-1.};
+                let row = Formation::Line {
+                    from: along * 25. * 4. / 3. + face_to * sign1 * 8.,
+                    to: along * 25. * 5. / 3. + face_to * sign1 * 8.,
+                    facing: Dir3::new(sign1 * face_to).unwrap()
+                };
+                for slot in row.slots(2) {
+                    let bench_pos = slot.pos;
                     commands.spawn((
                         SceneBundle {
                             scene: bench.clone(),
-                            transform: Transform::from_translation(bench_pos).looking_to(sign1 * face_to , Vec3::Y),
+                            transform: Transform::from_translation(bench_pos).looking_at(slot.look_at, Vec3::Y),
                             ..default()
                         },
-                        Bench
+                        Bench,
+                        Obstacle(BENCH_FOOTPRINT)
                     ))
                     .with_children(|bench| {
 
@@ -126,14 +136,15 @@ fn startup(
 
                     });
                     
-                    let lantern_pos = bench_pos - step.normalize() * 4.;
+                    let lantern_pos = bench_pos - along * 4.;
                     commands.spawn((
                         SceneBundle {
                             scene: lantern.clone(),
                             transform: Transform::from_translation(lantern_pos).looking_to(sign1 * face_to , Vec3::Y),
                             ..default()
                         },
-                        Lantern
+                        Lantern,
+                        Obstacle(LANTERN_FOOTPRINT)
                     ))
                     ;
 
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+
+use crate::{clock::ShowUpdate, ground::HeightField};
+
+pub struct FallingPlugin;
+impl Plugin for FallingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Wind>()
+        .add_event::<Landed>()
+        .add_systems(ShowUpdate, fall.run_if(any_with_component::<Falling>))
+        ;
+    }
+}
+
+// ---
+
+#[derive(Component)]
+pub struct Falling {
+    pub velocity: Vec3,
+    pub spin: Vec3,
+    pub terminal_velocity: f32,
+    pub glide: Vec3
+}
+
+impl Falling {
+    pub fn new(velocity: Vec3, spin: Vec3) -> Self {
+        Falling {
+            velocity,
+            spin,
+            terminal_velocity: TERMINAL_VELOCITY,
+            glide: Vec3::ZERO
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Wind(pub Vec3);
+
+impl Default for Wind {
+    fn default() -> Self {
+        Wind(Vec3::new(1.5, 0., 0.5))
+    }
+}
+
+#[derive(Event)]
+pub struct Landed(pub Entity);
+
+const GRAVITY: f32 = 9.81;
+pub const TERMINAL_VELOCITY: f32 = 20.;
+const WIND_DRAG: f32 = 0.5;
+pub const GROUND_Y: f32 = 0.;
+
+// ---
+
+pub fn fall(
+    mut falling_q: Query<(Entity, &mut Transform, &mut Falling)>,
+    wind: Res<Wind>,
+    field: Option<Res<HeightField>>,
+    mut landed_w: EventWriter<Landed>,
+    mut cmd: Commands,
+    time: Res<Time>
+) {
+    let dt = time.delta_seconds();
+    for (e, mut t, mut f) in falling_q.iter_mut() {
+        f.velocity.y = drag_step(f.velocity.y, f.terminal_velocity, dt);
+
+        // horizontally the body is carried by the wind, glide is its own speed relative to the air
+        let horizontal = f.velocity.with_y(0.);
+        let drift = (wind.0 + f.glide - horizontal) * (WIND_DRAG * dt).min(1.);
+        f.velocity += drift.with_y(0.);
+
+        t.translation += f.velocity * dt;
+        t.rotate(Quat::from_scaled_axis(f.spin * dt));
+
+        let ground = field.as_ref().and_then(|f| f.height(t.translation)).unwrap_or(GROUND_Y);
+        if t.translation.y <= ground {
+            t.translation.y = ground;
+            let forward = t.forward().with_y(0.);
+            let facing = if forward.length_squared() > f32::EPSILON {forward} else {Vec3::NEG_Z};
+            t.look_to(facing, Vec3::Y);
+            cmd.entity(e).remove::<Falling>();
+            landed_w.send(Landed(e));
+        }
+    }
+}
+
+// quadratic drag, so that the speed settles at the terminal velocity `vt`.
+// Solved exactly over the step, so the speed gets there at any frame time without overshooting,
+// from below as well as when thrown down faster, going up it slows down to the apex first
+
+fn drag_step(v: f32, vt: f32, dt: f32) -> f32 {
+    let mut dt = dt;
+    if v > 0. {
+        let up = (v / vt).atan();
+        let apex = vt / GRAVITY * up;
+        if dt < apex {
+            return vt * (up - GRAVITY * dt / vt).tan();
+        }
+        dt -= apex;
+    }
+    let s = (-v / vt).max(0.);
+    let tau = GRAVITY * dt / vt;
+    -vt * if s < 1. {
+        (s.atanh() + tau).tanh()
+    } else {
+        1. / ((1. / s).atanh() + tau).tanh()
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{event::ManualEventReader, system::RunSystemOnce};
+    use std::time::Duration;
+
+    fn world(wind: Vec3, dt: f32) -> World {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(dt));
+        world.insert_resource(time);
+        world.insert_resource(Wind(wind));
+        world.init_resource::<Events<Landed>>();
+        world
+    }
+
+    fn step(world: &mut World, n: usize) {
+        for _ in 0 .. n {
+            world.run_system_once(fall);
+        }
+    }
+
+    #[test]
+    fn settles_at_terminal_velocity() {
+        for dt in [1. / 60., 0.5, 2.] {
+            let mut world = world(Vec3::ZERO, dt);
+            let e = world.spawn((Transform::from_xyz(0., 1e6, 0.), Falling::new(Vec3::ZERO, Vec3::ZERO))).id();
+            let mut last = 0.;
+            for _ in 0 .. (30. / dt) as usize {
+                step(&mut world, 1);
+                let vy = world.get::<Falling>(e).unwrap().velocity.y;
+                assert!(vy <= last && vy >= -TERMINAL_VELOCITY, "{vy} after {last}, dt {dt}");
+                last = vy;
+            }
+            assert!((last + TERMINAL_VELOCITY).abs() < 1e-3, "{last}, dt {dt}");
+        }
+
+        // thrown down faster, the drag slows it down to the terminal velocity, not past it
+        let mut world = world(Vec3::ZERO, 1.);
+        let mut falling = Falling::new(Vec3::NEG_Y * 100., Vec3::ZERO);
+        falling.terminal_velocity = 4.;
+        let e = world.spawn((Transform::from_xyz(0., 1e6, 0.), falling)).id();
+        let mut last = -100.;
+        for _ in 0 .. 50 {
+            step(&mut world, 1);
+            let vy = world.get::<Falling>(e).unwrap().velocity.y;
+            assert!(vy >= last && vy <= -4., "{vy} after {last}");
+            last = vy;
+        }
+        assert!((last + 4.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn thrown_up_comes_down() {
+        let mut world = world(Vec3::ZERO, 0.25);
+        let e = world.spawn((Transform::from_xyz(0., 1e6, 0.), Falling::new(Vec3::Y * 30., Vec3::ZERO))).id();
+        let mut last = 30.;
+        for _ in 0 .. 100 {
+            step(&mut world, 1);
+            let vy = world.get::<Falling>(e).unwrap().velocity.y;
+            assert!(vy <= last && vy >= -TERMINAL_VELOCITY, "{vy} after {last}");
+            last = vy;
+        }
+        assert!((last + TERMINAL_VELOCITY).abs() < 1e-3);
+    }
+
+    #[test]
+    fn drifts_with_the_wind() {
+        let wind = Vec3::new(1.5, 0., 0.5);
+        let mut world = world(wind, 1. / 60.);
+        let e = world.spawn((Transform::from_xyz(0., 1e6, 0.), Falling::new(Vec3::new(-3., 0., 0.), Vec3::ZERO))).id();
+        step(&mut world, 60 * 20);
+        let velocity = world.get::<Falling>(e).unwrap().velocity;
+        assert!(velocity.with_y(0.).distance(wind) < 1e-2, "{velocity}");
+
+        // gliding is relative to the air
+        world.get_mut::<Falling>(e).unwrap().glide = Vec3::new(0., 0., -2.);
+        step(&mut world, 60 * 20);
+        let velocity = world.get::<Falling>(e).unwrap().velocity;
+        assert!(velocity.with_y(0.).distance(wind + Vec3::new(0., 0., -2.)) < 1e-2, "{velocity}");
+    }
+
+    #[test]
+    fn lands_on_the_ground() {
+        let mut world = world(Vec3::ZERO, 1. / 60.);
+        let e = world.spawn((
+            Transform::from_xyz(3., 10., 0.).looking_to(Vec3::new(1., -1., 0.), Vec3::Y),
+            Falling::new(Vec3::ZERO, Vec3::new(1., 2., 3.))
+        )).id();
+        let mut reader = ManualEventReader::<Landed>::default();
+        let mut frames = 0;
+        while world.get::<Falling>(e).is_some() {
+            step(&mut world, 1);
+            frames += 1;
+            assert!(frames < 600);
+        }
+        let t = world.get::<Transform>(e).unwrap();
+        assert_eq!(t.translation.y, GROUND_Y);
+        // upright when landed
+        assert!(t.up().dot(Vec3::Y) > 0.999);
+        let landed: Vec<Entity> = reader.read(world.resource::<Events<Landed>>()).map(|l| l.0).collect();
+        assert_eq!(landed, [e]);
+    }
+}
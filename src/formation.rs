@@ -0,0 +1,246 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    movement::{MovementPath, MovementPathBuilder},
+    shared::min_cost_assignment
+};
+
+// ---
+
+// slot layouts for a crowd, every slot comes with the point the agent should finally look at,
+// circles, rings and the spiral look at their center, the rest look along `facing`
+
+#[derive(Clone, Copy)]
+pub enum Formation {
+    Circle {center: Vec3, radius: f32},
+    // every next ring is `spacing` closer to the center, slots alternate between the rings
+    Rings {center: Vec3, radius: f32, rings: usize, spacing: f32},
+    Grid {center: Vec3, columns: usize, spacing: f32, facing: Dir3},
+    Line {from: Vec3, to: Vec3, facing: Dir3},
+    // `angle` is the full opening of the V, the apex is the first slot
+    V {apex: Vec3, facing: Dir3, spacing: f32, angle: f32},
+    // archimedean, `spacing` both between turns and between neighbours on the curve
+    Spiral {center: Vec3, spacing: f32}
+}
+
+#[derive(Clone, Copy)]
+pub struct Slot {
+    pub pos: Vec3,
+    pub look_at: Vec3
+}
+
+// ---
+
+impl Formation {
+    pub fn slots(&self, n: usize) -> Vec<Slot> {
+        (0 .. n).map(|i| self.slot(i, n)).collect()
+    }
+
+    fn slot(&self, i: usize, n: usize) -> Slot {
+        let around = |center: Vec3, radius: f32, a: f32| Slot {
+            pos: center + Vec3::new(radius * a.cos(), 0., radius * a.sin()),
+            look_at: center
+        };
+        let ahead = |pos: Vec3, facing: Dir3| Slot {
+            pos,
+            look_at: pos + *facing
+        };
+
+        match *self {
+            Formation::Circle {center, radius} => around(center, radius, TAU * i as f32 / n as f32),
+            Formation::Rings {center, radius, rings, spacing} => {
+                let ring = i % rings.max(1);
+                around(center, radius - ring as f32 * spacing, TAU * i as f32 / n as f32)
+            },
+            Formation::Grid {center, columns, spacing, facing} => {
+                let columns = columns.max(1);
+                let rows = n.div_ceil(columns);
+                let (row, column) = (i / columns, i % columns);
+                let right = facing.cross(Vec3::Y);
+                let x = (column as f32 - (columns - 1) as f32 * 0.5) * spacing;
+                let z = (row as f32 - (rows.max(1) - 1) as f32 * 0.5) * spacing;
+                ahead(center + right * x - *facing * z, facing)
+            },
+            Formation::Line {from, to, facing} => {
+                ahead(from.lerp(to, i as f32 / n.saturating_sub(1).max(1) as f32), facing)
+            },
+            Formation::V {apex, facing, spacing, angle} => {
+                let arm = i.div_ceil(2);
+                let side = if i % 2 == 1 {-1.} else {1.};
+                let back = Quat::from_rotation_y(side * angle * 0.5) * -*facing;
+                ahead(apex + back * arm as f32 * spacing, facing)
+            },
+            Formation::Spiral {center, spacing} => {
+                // with r = spacing * a / TAU the arc length is about spacing * a^2 / 2TAU
+                let a = (2. * TAU * (i + 1) as f32).sqrt();
+                around(center, spacing * a / TAU, a)
+            }
+        }
+    }
+
+    // every agent gets its own slot so that the total of squared distances is minimal,
+    // agents are taken in entity order so the result doesn't depend on the query order
+
+    pub fn assign(&self, agents: &[(Entity, Vec3)]) -> Vec<(Entity, Slot)> {
+        let mut agents = agents.to_vec();
+        agents.sort_by_key(|(e, _)| *e);
+        let slots = self.slots(agents.len());
+        let costs: Vec<Vec<f32>> = agents.iter()
+            .map(|(_, p)| slots.iter().map(|s| s.pos.distance_squared(*p)).collect())
+            .collect();
+        min_cost_assignment(&costs)
+            .into_iter()
+            .zip(agents)
+            .map(|(j, (e, _))| (e, slots[j]))
+            .collect()
+    }
+
+    // single point paths to the slots, to be decorated by the caller (animation, turn speed ...)
+
+    pub fn paths(&self, agents: &[(Entity, Vec3)], velocity: f32) -> Vec<(Entity, MovementPathBuilder)> {
+        self.assign(agents)
+            .into_iter()
+            .map(|(e, slot)| (e, MovementPath::builder().to(slot.pos, velocity).finish_look_at(slot.look_at)))
+            .collect()
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn circle() {
+        let center = Vec3::new(1., 0., 2.);
+        let slots = Formation::Circle {center, radius: 3.}.slots(4);
+        assert!(close(slots[0].pos, center + Vec3::X * 3.));
+        assert!(close(slots[1].pos, center + Vec3::Z * 3.));
+        assert!(close(slots[2].pos, center - Vec3::X * 3.));
+        assert!(slots.iter().all(|s| s.look_at == center));
+    }
+
+    #[test]
+    fn rings() {
+        let slots = Formation::Rings {center: Vec3::ZERO, radius: 6., rings: 2, spacing: 1.5}.slots(8);
+        for (i, s) in slots.iter().enumerate() {
+            let radius = if i % 2 == 0 {6.} else {4.5};
+            assert!((s.pos.length() - radius).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn grid() {
+        let slots = Formation::Grid {center: Vec3::ZERO, columns: 3, spacing: 2., facing: Dir3::NEG_Z}.slots(5);
+        // rows are filled from the front, centered on both axes
+        assert!(close(slots[0].pos, Vec3::new(-2., 0., -1.)));
+        assert!(close(slots[2].pos, Vec3::new(2., 0., -1.)));
+        assert!(close(slots[3].pos, Vec3::new(-2., 0., 1.)));
+        assert!(close(slots[4].pos, Vec3::new(0., 0., 1.)));
+        assert!(slots.iter().all(|s| close(s.look_at - s.pos, Vec3::NEG_Z)));
+    }
+
+    #[test]
+    fn line() {
+        let (from, to) = (Vec3::new(-3., 0., 1.), Vec3::new(3., 0., 1.));
+        let slots = Formation::Line {from, to, facing: Dir3::Z}.slots(4);
+        assert!(close(slots[0].pos, from));
+        assert!(close(slots[1].pos, Vec3::new(-1., 0., 1.)));
+        assert!(close(slots[3].pos, to));
+        assert!(slots.iter().all(|s| close(s.look_at - s.pos, Vec3::Z)));
+        // a single slot stands at the start
+        assert!(close(Formation::Line {from, to, facing: Dir3::Z}.slots(1)[0].pos, from));
+    }
+
+    #[test]
+    fn v() {
+        let slots = Formation::V {apex: Vec3::ZERO, facing: Dir3::NEG_Z, spacing: 2., angle: FRAC_PI_2}.slots(5);
+        assert!(close(slots[0].pos, Vec3::ZERO));
+        // the arms alternate and trail behind the apex at half the opening each
+        let arm = Vec2::new(1., 1.).normalize() * 2.;
+        assert!(close(slots[1].pos, Vec3::new(-arm.x, 0., arm.y)));
+        assert!(close(slots[2].pos, Vec3::new(arm.x, 0., arm.y)));
+        assert!(close(slots[3].pos, Vec3::new(-arm.x, 0., arm.y) * 2.));
+        assert!(close(slots[4].pos, Vec3::new(arm.x, 0., arm.y) * 2.));
+    }
+
+    #[test]
+    fn spiral() {
+        let slots = Formation::Spiral {center: Vec3::ZERO, spacing: 1.}.slots(40);
+        let radii: Vec<f32> = slots.iter().map(|s| s.pos.length()).collect();
+        assert!(radii.windows(2).all(|w| w[1] > w[0]));
+        // neighbours keep about `spacing` apart, away from the tight center
+        for w in slots[10 ..].windows(2) {
+            let d = w[0].pos.distance(w[1].pos);
+            assert!((d - 1.).abs() < 0.1, "{d}");
+        }
+        assert!(slots.iter().all(|s| s.look_at == Vec3::ZERO));
+    }
+
+    #[test]
+    fn assigns_the_least_total_distance() {
+        let formation = Formation::Line {from: Vec3::ZERO, to: Vec3::X * 3., facing: Dir3::Z};
+        let mut world = World::new();
+        let agents: Vec<(Entity, Vec3)> = [
+            Vec3::new(3.2, 0., 1.),
+            Vec3::new(0.9, 0., -1.),
+            Vec3::new(-0.5, 0., 0.),
+            Vec3::new(2.1, 0., 0.5)
+        ].into_iter().map(|p| (world.spawn_empty().id(), p)).collect();
+
+        let total = |assigned: &[(Entity, Slot)]| -> f32 {
+            assigned.iter()
+                .map(|(e, s)| agents.iter().find(|(a, _)| a == e).unwrap().1.distance_squared(s.pos))
+                .sum()
+        };
+        let paths = formation.paths(&agents, 1.);
+        let assigned = formation.assign(&agents);
+        assert_eq!(paths.len(), 4);
+
+        // nothing beats it, every permutation of the slots checked
+        let slots = formation.slots(4);
+        let best = permutations(4).into_iter()
+            .map(|p| agents.iter().zip(p).map(|((_, a), j)| a.distance_squared(slots[j].pos)).sum::<f32>())
+            .fold(f32::INFINITY, f32::min);
+        assert!((total(&assigned) - best).abs() < 1e-4);
+        assert!(close(assigned.iter().find(|(e, _)| *e == agents[2].0).unwrap().1.pos, Vec3::ZERO));
+
+        // the paths lead there
+        for ((e, path), (ae, slot)) in paths.into_iter().zip(assigned.iter()) {
+            assert_eq!(e, *ae);
+            let mp = path.build().unwrap();
+            assert_eq!(mp.points()[0].pos, slot.pos);
+        }
+
+        // the order agents come in doesn't matter
+        let mut shuffled = agents.clone();
+        shuffled.reverse();
+        shuffled.swap(0, 2);
+        let again = formation.assign(&shuffled);
+        for ((e, a), (f, b)) in assigned.iter().zip(again.iter()) {
+            assert_eq!(e, f);
+            assert_eq!(a.pos, b.pos);
+        }
+    }
+
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![Vec::new()];
+        }
+        permutations(n - 1).into_iter()
+            .flat_map(|p| (0 ..= p.len()).map(move |i| {
+                let mut p = p.clone();
+                p.insert(i, n - 1);
+                p
+            }))
+            .collect()
+    }
+}
@@ -1,13 +1,17 @@
 
 use bevy::prelude::*;
 use crate::{
-    animator::{AllAnimations, AnimationKey, CurrentAnimation}, 
+    animator::{AllAnimations, AnimationKey, Clip, CurrentAnimation, Locomotion, Variety}, 
     camera_target::{CameraTarget, SetCameraTarget}, 
+    clock::ShowTime,
     env::{History, Humidity, Seat}, 
-    movement::{MovementPath, MovementPathDone}, music::MusicEvent, GameState
+    formation::Formation,
+    ground::GroundFollow,
+    movement::{Easing, MovementCommandsExt, MovementPath, MovementPathCancelled, MovementPathDone, MovementPaused, PathInterpolation}, music::{MusicEvent, MusicTimeline}, 
+    nav::NavGrid, steering::Separation, GameState
 };
 
-use crate::shared::random_pos;
+use crate::shared::{random_pos, ShowRng};
 
 // ---
 
@@ -33,28 +37,61 @@ impl Plugin for GirlsPlugin {
         .add_systems(OnEnter(GirlsState::Idle), enter_idle)
         .add_systems(OnEnter(GameState::Portal), enter_running)
         
-        .add_systems(Update, move_done.run_if(on_event::<MovementPathDone>()))
+        .add_systems(Update, move_done
+            .run_if(on_event::<MovementPathDone>().or_else(on_event::<MovementPathCancelled>()))
+        )
+        .add_systems(OnEnter(GameState::Speak), enter_speak)
         .add_systems(OnEnter(GameState::Sing), enter_sing)
         .add_systems(OnEnter(GameState::Raining), enter_raining)
-        .add_event::<MovementPathDone>()
         .observe(music_event)
         ;
     }
 } 
 
+// clips of girl.glb (the leader uses it too), the file has no clip names,
+// so they are registered under the names the show uses them by
+
+const GIRL_CLIPS: [(&str, usize); 12] = [
+    ("speak", 0), ("run", 1), ("dance", 2), ("leader-dance", 3), ("idle", 4), ("walk", 5),
+    ("sing", 6), ("dance-2", 7), ("dance-3", 8), ("rest", 9), ("rest-2", 10), ("fly", 12)
+];
+
+pub const GIRL_SPEAK: &str = "speak";
+pub const GIRL_RUN: &str = "run";
+pub const GIRL_IDLE: &str = "idle";
+pub const GIRL_WALK: &str = "walk";
+pub const GIRL_SING: &str = "sing";
+pub const GIRL_FLY: &str = "fly";
+pub const GIRL_DANCE: &str = "dance";
+pub const GIRL_LEADER_DANCE: &str = "leader-dance";
+pub const GIRL_REST: &str = "rest";
+const GIRL_DANCES: [&str; 4] = [GIRL_DANCE, GIRL_LEADER_DANCE, "dance-2", "dance-3"];
+const GIRL_RESTS: [&str; 2] = [GIRL_REST, "rest-2"];
+
 const GIRLS_COUNT : usize = 64;
 pub const GIRL_VELOCITY: f32 = 2.;
+pub const GIRL_TURN_SPEED: f32 = 8.;
+const GIRL_RADIUS: f32 = 0.35;
+const GIRL_WALK_STRIDE: f32 = 2.5;
+const GIRL_RUN_STRIDE: f32 = 5.;
+const GIRL_SPEED_JITTER: f32 = 0.15;
 
 // ---
 
 fn spawn (
     mut commands: Commands,
     assets: ResMut<AssetServer>,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
     mut all_animations: ResMut<AllAnimations>,
-
+    mut rng: ResMut<ShowRng>,
 ) {
-    all_animations.add(AnimationKey::Girl, "models/girls/girl.glb", 13, &mut graphs, &assets);
+    all_animations.add(AnimationKey::Girl, "models/girls/girl.glb", Some(13), &assets);
+    all_animations.alias(AnimationKey::Girl, &GIRL_CLIPS);
+    all_animations.set_locomotion(AnimationKey::Girl, Locomotion {
+        walk: Clip::Name(GIRL_WALK), walk_speed: GIRL_WALK_STRIDE,
+        run: Clip::Name(GIRL_RUN), run_speed: GIRL_RUN_STRIDE
+    });
+    all_animations.set_variety(AnimationKey::Girl, Variety {random_phase: true, speed_jitter: GIRL_SPEED_JITTER});
+    all_animations.set_idles(AnimationKey::Girl, &[(Clip::Name(GIRL_IDLE), 2.), (Clip::Name(GIRL_SING), 1.)]);
     let gh = assets.load("models/girls/girl.glb#Scene0");
     let far = 60.;
     for bp  in [ Vec3::new(0., 0., far), Vec3::new(0., 0., -far), Vec3::new(far, 0., 0.), Vec3::new(-far, 0.,0.)] {
@@ -62,11 +99,13 @@ fn spawn (
             commands.spawn((
                 SceneBundle {
                     scene: gh.clone(),
-                    transform: Transform::from_translation(random_pos(bp, 10.)),
+                    transform: Transform::from_translation(random_pos(&mut rng.0, bp, 10.)),
                     ..default()
                 },
                 Girl,
-                AnimationKey::Girl
+                GroundFollow,
+                AnimationKey::Girl,
+                Separation::new(GIRL_RADIUS, 1.)
             ));
         }        
     }
@@ -77,6 +116,8 @@ fn spawn (
 fn enter_walking(
     mut girls_q: Query<(&mut Transform, Entity), With<Girl>>,
     seats_q: Query<&GlobalTransform, (With<Seat>, Without<Girl>)>,
+    grid: Res<NavGrid>,
+    mut rng: ResMut<ShowRng>,
     mut ccc: Commands,
     mut next : ResMut<NextState<GirlsState>>
 ) {
@@ -92,13 +133,22 @@ fn enter_walking(
             .map(|(idx, _)| idx)
             .unwrap();
         let seat  = seats.swap_remove(idx);
-        let velocity =  fastrand::f32() +  GIRL_VELOCITY;
-        let points = vec![
-            (seat.0 - seat.1 * 4.0, velocity * 2., Some(1), None),
-            (seat.0, velocity, Some(5), None)
-        ];
-        ccc.entity(entity).insert(
-            MovementPath::new(&points, fastrand::i32(9..=10) as usize, points[0].0)
+        let velocity =  rng.0.f32() +  GIRL_VELOCITY;
+        let approach = seat.0 - seat.1 * 4.0;
+        let route = grid.find_path(t.translation, approach).unwrap_or(vec![approach]);
+        let mut path = MovementPath::builder();
+        for (i, p) in route.into_iter().enumerate() {
+            path = path.to(p, velocity * 2.);
+            if i == 0 {
+                path = path.animation(GIRL_RUN).easing(Easing::In);
+            }
+        }
+        ccc.entity(entity).start_path(
+            path
+            .to(seat.0, velocity).animation(GIRL_WALK).easing(Easing::Out)
+            .turn_speed(GIRL_TURN_SPEED)
+            .finish_animation(GIRL_RESTS[rng.0.usize(0 .. GIRL_RESTS.len())])
+            .finish_look_at(approach)
         );
     }
     next.set(GirlsState::Moving);
@@ -109,12 +159,15 @@ fn enter_walking(
 fn enter_idle(
     state: Res<State<GameState>>,
     mut girls_q: Query<&mut CurrentAnimation, With<Girl>>,
+    all_animations: Res<AllAnimations>,
+    mut rng: ResMut<ShowRng>,
 ) {
-    let idxs = [4, 6];
     if *state.get() == GameState::Speak {
 
         for mut ca in girls_q.iter_mut() {
-            ca.0 = idxs[fastrand::usize(0 ..idxs.len())];
+            if let Some(idle) = all_animations.pick_idle(&AnimationKey::Girl, &mut rng.0) {
+                ca.0 = idle;
+            }
         }
     }
 }
@@ -122,26 +175,30 @@ fn enter_idle(
 // ----
 
 fn enter_running(
-    mut girls_q: Query<(&Transform, Entity), With<Girl>>,
+    girls_q: Query<(&Transform, Entity), With<Girl>>,
+    mut rng: ResMut<ShowRng>,
     mut cc: Commands,
     mut next : ResMut<NextState<GirlsState>>
 ) {
-
-    let step = 360.0 / GIRLS_COUNT as f32; 
-    let idxs = [2,3,7,8];
-    for (idx, (t, entity)) in girls_q.iter_mut().enumerate() {
-        let radius = 6. * if idx % 2 == 0 {1.} else {0.75};
-
-        let delta  = t.forward() *  (1.0  +  fastrand::f32());
-        let velocity =  fastrand::f32() +  GIRL_VELOCITY;
-        let points = vec![
-            (t.translation + delta, velocity * 2., Some(1), None),
-            (-delta * 4.0, velocity * 2., None, None),
-            (Vec3::new((radius - fastrand::f32() * 1.0 ) * (idx as f32 * step).cos(), 0.0, radius * (idx  as f32 * step).sin()), velocity * 2. , None, None)
-        ];
+    let formation = Formation::Rings {center: Vec3::ZERO, radius: 6., rings: 2, spacing: 1.5};
+    let agents: Vec<(Entity, Vec3)> = girls_q.iter().map(|(t, e)| (e, t.translation)).collect();
+    for (entity, slot) in formation.assign(&agents) {
+        let Ok((t, _)) = girls_q.get(entity) else {
+            continue;
+        };
+        let delta  = t.forward() *  (1.0  +  rng.0.f32());
+        let velocity =  rng.0.f32() +  GIRL_VELOCITY;
+        let place = slot.pos - (slot.pos - slot.look_at).normalize_or_zero() * rng.0.f32();
         
-        cc.entity(entity).insert(
-            MovementPath::new(&points,idxs[fastrand::usize(0 .. idxs.len())], Vec3::ZERO)
+        cc.entity(entity).start_path(
+            MovementPath::builder()
+            .to(t.translation + delta, velocity * 2.).animation(GIRL_RUN)
+            .to(-delta * 4.0, velocity * 2.)
+            .to(place, velocity * 2.)
+            .interpolation(PathInterpolation::CatmullRom)
+            .turn_speed(GIRL_TURN_SPEED)
+            .finish_animation(GIRL_DANCES[rng.0.usize(0 .. GIRL_DANCES.len())])
+            .finish_look_at(slot.look_at)
         );
     }
     next.set(GirlsState::Moving);
@@ -150,13 +207,16 @@ fn enter_running(
 // ---
 
 fn move_done(
-    mut ev_r : EventReader<MovementPathDone>,
+    mut done_r : EventReader<MovementPathDone>,
+    mut cancelled_r : EventReader<MovementPathCancelled>,
     girls_q: Query<Entity, With<Girl>>,
     check_q: Query<Entity, (With<Girl>, With<MovementPath>)>,
     mut next: ResMut<NextState<GirlsState>>
 ) {
-    for e in ev_r.read() {
-        if girls_q.get(e.0).is_ok() {
+    let done = done_r.read().map(|e| e.0);
+    let cancelled = cancelled_r.read().map(|e| e.0);
+    for e in done.chain(cancelled) {
+        if girls_q.get(e).is_ok() {
             if check_q.is_empty() {
                 next.set(GirlsState::Idle);
             }
@@ -166,18 +226,48 @@ fn move_done(
 
 // ---
 
+// the ones still on their way stop and listen to the leader
+
+fn enter_speak(
+    mut girls_q: Query<(Entity, &mut CurrentAnimation, Has<MovementPath>), With<Girl>>,
+    mut cmd: Commands
+) {
+    for (e, mut ca, moving) in girls_q.iter_mut() {
+        if moving {
+            ca.0 = Clip::Name(GIRL_IDLE);
+            cmd.entity(e).pause_movement();
+        }
+    }
+}
+
+// ---
+
 fn enter_sing (
-    mut girls_q: Query<&mut CurrentAnimation, With<Girl>>,
+    mut girls_q: Query<(Entity, &mut CurrentAnimation, Has<MovementPaused>), With<Girl>>,
+    mut cmd: Commands
 ) {
-    girls_q.iter_mut().for_each(|mut ca| ca.0 = 6);
+    for (e, mut ca, paused) in girls_q.iter_mut() {
+        ca.0 = Clip::Name(GIRL_SING);
+        if paused {
+            cmd.entity(e).resume_movement();
+        }
+    }
 }
 
 // ---
 
+// the rain stops everyone where they are, figure or not
+
 fn enter_raining(
-    mut girls_q: Query<&mut CurrentAnimation, With<Girl>>,
+    mut girls_q: Query<(Entity, &mut CurrentAnimation, Has<MovementPath>), With<Girl>>,
+    mut cmd: Commands
 ) {
-    girls_q.iter_mut().for_each(|mut ca| ca.0 = 4);
+    for (e, mut ca, moving) in girls_q.iter_mut() {
+        ca.0 = Clip::Name(GIRL_IDLE);
+        if moving {
+            cmd.entity(e).cancel_movement();
+        }
+    }
 }
 
 //  ---
@@ -185,12 +275,36 @@ fn enter_raining(
 fn music_event (
     trigger: Trigger<MusicEvent>,
     mut cmd: Commands,
+    girls_q: Query<(Entity, &Transform), With<Girl>>,
+    timeline: Res<MusicTimeline>,
+    time: ShowTime,
     h_q: Query<Entity, With<Humidity>>,
     hi_q: Query<Entity, With<History>>
 ) {
     let MusicEvent(GameState::Sing, count) = *trigger.event() else  {
         return;
     };
+
+    // every line of the verse the girls walk over into another figure, in place for the next line
+    let formation = match count {
+        2 => Some(Formation::Circle {center: Vec3::ZERO, radius: 10.}),
+        3 => Some(Formation::V {apex: Vec3::new(0., 0., 4.), facing: Dir3::NEG_Z, spacing: 1., angle: 1.}),
+        4 => Some(Formation::Grid {center: Vec3::ZERO, columns: 8, spacing: 2., facing: Dir3::NEG_X}),
+        5 => Some(Formation::Spiral {center: Vec3::ZERO, spacing: 1.5}),
+        _ => None
+    };
+    if let Some(formation) = formation {
+        let agents: Vec<(Entity, Vec3)> = girls_q.iter().map(|(e, t)| (e, t.translation)).collect();
+        let next_line = timeline.position(&time.now()).and_then(|song| timeline.next_cue(song));
+        for (e, path) in formation.paths(&agents, GIRL_VELOCITY) {
+            let path = path.animation(GIRL_WALK).turn_speed(GIRL_TURN_SPEED).finish_animation(GIRL_SING);
+            cmd.entity(e).start_path(match next_line {
+                Some(at) => path.arrive_at(at),
+                None => path
+            });
+        }
+    }
+
     if count == 1 {
         let he = h_q.get_single().unwrap();
         cmd.trigger(SetCameraTarget(
@@ -0,0 +1,404 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{PrimitiveTopology, VertexAttributeValues},
+    scene::{SceneInstance, SceneSpawner},
+    utils::{HashMap, HashSet}
+};
+use bevy_gltf_components::GltfComponentsSet;
+
+use crate::{
+    clock::ShowUpdate,
+    falling::Falling,
+    movement::{do_moving, MovementPath},
+    NotReady
+};
+
+pub struct GroundPlugin;
+impl Plugin for GroundPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Startup, setup)
+        // platforms are marked by components from the glTF, they must be in place before baking
+        .add_systems(Update, bake
+            .after(GltfComponentsSet::Injection)
+            .run_if(not(resource_exists::<HeightField>))
+        )
+        .add_systems(ShowUpdate, (
+            platforms,
+            follow_ground.after(do_moving)
+        ).chain().run_if(resource_exists::<HeightField>))
+        ;
+    }
+}
+
+// ---
+
+// scenes whose walkable surfaces are baked into the height field
+
+#[derive(Component)]
+pub struct Ground;
+
+// opt-in, keeps a walker on the surface, steps up to MAX_STEP are climbed,
+// higher differences (a lift ride, a jump) are left alone
+
+#[derive(Component)]
+pub struct GroundFollow;
+
+// a moving part of a Ground scene (a turning lift platform), left out of the bake
+// and rasterized again every frame where it is now
+
+#[derive(Component)]
+pub struct Platform;
+
+// every cell keeps all walkable layers under it, sorted bottom up,
+// so that a bridge or a platform doesn't hide the floor below
+
+#[derive(Resource)]
+pub struct HeightField {
+    origin: Vec2,
+    cell: f32,
+    width: usize,
+    layers: Vec<Vec<f32>>,
+    platforms: HashMap<usize, Vec<f32>>
+}
+
+#[derive(Component)]
+struct Baking;
+
+const FIELD_HALF_EXTENT: f32 = 80.;
+const FIELD_CELL: f32 = 0.5;
+const MIN_WALKABLE_NORMAL: f32 = 0.6;
+const LAYER_MERGE: f32 = 0.05;
+const MAX_STEP: f32 = 0.5;
+const PATH_SNAP: f32 = 1.5;
+const GROUND_SMOOTHING: f32 = 15.;
+
+// ---
+
+impl HeightField {
+    fn new(half_extent: f32, cell: f32) -> Self {
+        let width = (2. * half_extent / cell).ceil() as usize;
+        HeightField {
+            origin: Vec2::splat(-half_extent),
+            cell,
+            width,
+            layers: vec![Vec::new(); width * width],
+            platforms: HashMap::new()
+        }
+    }
+
+    fn idx(&self, p: Vec2) -> Option<usize> {
+        let local = ((p - self.origin) / self.cell).floor();
+        if local.x < 0. || local.y < 0. || local.x >= self.width as f32 || local.y >= self.width as f32 {
+            return None;
+        }
+        Some(local.y as usize * self.width + local.x as usize)
+    }
+
+    fn add(&mut self, i: usize, h: f32) {
+        let layers = &mut self.layers[i];
+        if layers.iter().any(|l| (l - h).abs() < LAYER_MERGE) {
+            return;
+        }
+        let at = layers.partition_point(|l| *l < h);
+        layers.insert(at, h);
+    }
+
+    fn add_triangle(&mut self, a: Vec3, b: Vec3, c: Vec3) {
+        for (i, h) in self.cells(a, b, c) {
+            self.add(i, h);
+        }
+    }
+
+    fn add_platform_triangle(&mut self, a: Vec3, b: Vec3, c: Vec3) {
+        for (i, h) in self.cells(a, b, c) {
+            self.platforms.entry(i).or_default().push(h);
+        }
+    }
+
+    // the cells whose centers a walkable triangle covers, with its height there
+
+    fn cells(&self, a: Vec3, b: Vec3, c: Vec3) -> Vec<(usize, f32)> {
+        let mut cells = Vec::new();
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        if normal.y < MIN_WALKABLE_NORMAL {
+            return cells;
+        }
+        let (a2, b2, c2) = (a.xz(), b.xz(), c.xz());
+        let area = (b2 - a2).perp_dot(c2 - a2);
+        if area.abs() <= f32::EPSILON {
+            return cells;
+        }
+        let min = ((a2.min(b2).min(c2) - self.origin) / self.cell).floor().max(Vec2::ZERO);
+        let max = ((a2.max(b2).max(c2) - self.origin) / self.cell).ceil().min(Vec2::splat(self.width as f32));
+        for y in min.y as usize .. max.y as usize {
+            for x in min.x as usize .. max.x as usize {
+                let p = self.origin + (Vec2::new(x as f32, y as f32) + 0.5) * self.cell;
+                let wa = (b2 - p).perp_dot(c2 - p) / area;
+                let wb = (c2 - p).perp_dot(a2 - p) / area;
+                let wc = 1. - wa - wb;
+                if wa < 0. || wb < 0. || wc < 0. {
+                    continue;
+                }
+                cells.push((y * self.width + x, wa * a.y + wb * b.y + wc * c.y));
+            }
+        }
+        cells
+    }
+
+    fn surfaces(&self, i: usize) -> impl Iterator<Item = f32> + '_ {
+        self.layers[i].iter().chain(self.platforms.get(&i).into_iter().flatten()).copied()
+    }
+
+    // the highest surface a walker at `pos` can stand on
+
+    pub fn height(&self, pos: Vec3) -> Option<f32> {
+        let i = self.idx(pos.xz())?;
+        self.surfaces(i).filter(|h| *h <= pos.y + MAX_STEP).reduce(f32::max)
+    }
+
+    // `pos` moved onto the surface under it, unless it is clearly meant to be in the air
+
+    pub fn ground(&self, pos: Vec3) -> Vec3 {
+        let Some(i) = self.idx(pos.xz()) else {
+            return pos;
+        };
+        self.surfaces(i)
+            .filter(|h| (h - pos.y).abs() <= PATH_SNAP)
+            .min_by(|a, b| (a - pos.y).abs().total_cmp(&(b - pos.y).abs()))
+            .map_or(pos, |h| pos.with_y(h))
+    }
+}
+
+// ---
+
+fn setup(
+    mut cmd: Commands
+) {
+    cmd.spawn((Baking, NotReady));
+}
+
+// ---
+
+fn bake(
+    ground_q: Query<(Entity, Option<&SceneInstance>), With<Ground>>,
+    children_q: Query<&Children>,
+    parts_q: Query<(Option<&Handle<Mesh>>, &GlobalTransform, Has<Platform>)>,
+    baking_q: Query<Entity, With<Baking>>,
+    spawner: Res<SceneSpawner>,
+    meshes: Res<Assets<Mesh>>,
+    mut cmd: Commands
+) {
+    if ground_q.iter().any(|(_, si)| !si.is_some_and(|si| spawner.instance_is_ready(**si))) {
+        return;
+    }
+    let scenery = || ground_q.iter().flat_map(|(e, _)| children_q.iter_descendants(e));
+    let moving: HashSet<Entity> = scenery()
+        .filter(|e| parts_q.get(*e).is_ok_and(|(_, _, platform)| platform))
+        .flat_map(|e| std::iter::once(e).chain(children_q.iter_descendants(e)))
+        .collect();
+    let parts: Vec<(&Mesh, &GlobalTransform)> = scenery()
+        .filter(|e| !moving.contains(e))
+        .filter_map(|e| parts_q.get(e).ok())
+        .filter_map(|(h, gt, _)| meshes.get(h?).map(|m| (m, gt)))
+        .collect();
+
+    // nothing to stand on, everyone keeps to GROUND_Y, but loading goes on
+    if parts.is_empty() {
+        warn!("no ground meshes to bake, the height field stays flat");
+    }
+    let mut field = HeightField::new(FIELD_HALF_EXTENT, FIELD_CELL);
+    for (mesh, gt) in parts {
+        for [a, b, c] in triangles(mesh, gt) {
+            field.add_triangle(a, b, c);
+        }
+    }
+    cmd.insert_resource(field);
+    for e in baking_q.iter() {
+        cmd.entity(e).despawn();
+    }
+}
+
+fn triangles(mesh: &Mesh, gt: &GlobalTransform) -> Vec<[Vec3; 3]> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Vec::new();
+    }
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+        return Vec::new();
+    };
+    let world: Vec<Vec3> = positions.iter().map(|p| gt.transform_point(Vec3::from(*p))).collect();
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0 .. world.len()).collect()
+    };
+    indices.chunks_exact(3).map(|tri| [world[tri[0]], world[tri[1]], world[tri[2]]]).collect()
+}
+
+// ---
+
+fn platforms(
+    platforms_q: Query<Entity, With<Platform>>,
+    children_q: Query<&Children>,
+    meshes_q: Query<(&Handle<Mesh>, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+    mut field: ResMut<HeightField>
+) {
+    field.platforms.clear();
+    for e in platforms_q.iter() {
+        for part in std::iter::once(e).chain(children_q.iter_descendants(e)) {
+            let Some((mesh, gt)) = meshes_q.get(part).ok().and_then(|(h, gt)| meshes.get(h).zip(Some(gt))) else {
+                continue;
+            };
+            for [a, b, c] in triangles(mesh, gt) {
+                field.add_platform_triangle(a, b, c);
+            }
+        }
+    }
+}
+
+// ---
+
+fn follow_ground(
+    mut walkers_q: Query<(&mut Transform, Option<&MovementPath>), (With<GroundFollow>, Without<Falling>)>,
+    field: Res<HeightField>,
+    time: Res<Time>
+) {
+    for (mut t, mp) in walkers_q.iter_mut() {
+        // heading for a point in the air, a climb that is not ours to undo
+        if let Some(mp) = mp {
+            let target = mp.current().pos;
+            if field.height(target).is_some_and(|h| target.y - h > MAX_STEP) {
+                continue;
+            }
+        }
+        let Some(h) = field.height(t.translation) else {
+            continue;
+        };
+        if t.translation.y - h > MAX_STEP {
+            continue;
+        }
+        t.translation.y += (h - t.translation.y) * (time.delta_seconds() * GROUND_SMOOTHING).min(1.);
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::{clock::ClockPlugin, movement::{MovementCommandsExt, MovementPlugin}, music::MusicTimeline};
+
+    fn quad(field: &mut HeightField, min: Vec2, max: Vec2, y: f32) {
+        let (a, b, c, d) = (min.extend(y).xzy(), Vec3::new(max.x, y, min.y), max.extend(y).xzy(), Vec3::new(min.x, y, max.y));
+        // wound so that the normal faces up
+        field.add_triangle(a, d, c);
+        field.add_triangle(a, c, b);
+    }
+
+    // a floor with a bridge 3 m up across the middle
+
+    fn field() -> HeightField {
+        let mut field = HeightField::new(4., 0.5);
+        quad(&mut field, Vec2::splat(-4.), Vec2::splat(4.), 0.);
+        quad(&mut field, Vec2::new(-4., -1.), Vec2::new(4., 1.), 3.);
+        field
+    }
+
+    #[test]
+    fn keeps_every_layer() {
+        let field = field();
+        assert_eq!(field.height(Vec3::new(0., 0., 0.)), Some(0.));
+        assert_eq!(field.height(Vec3::new(0., 3., 0.)), Some(3.));
+        // a step up is climbed, the bridge above the head is not
+        assert_eq!(field.height(Vec3::new(0., 2.6, 0.)), Some(3.));
+        assert_eq!(field.height(Vec3::new(0., 1., 0.)), Some(0.));
+        // beside the bridge there is only the floor
+        assert_eq!(field.height(Vec3::new(0., 3., 2.)), Some(0.));
+        // under the floor and off the field
+        assert_eq!(field.height(Vec3::new(0., -1., 0.)), None);
+        assert_eq!(field.height(Vec3::new(10., 0., 0.)), None);
+    }
+
+    #[test]
+    fn walls_and_slopes() {
+        let mut field = HeightField::new(4., 0.5);
+        // a ramp from 0 to 1 over 4 m
+        field.add_triangle(Vec3::new(-2., 0., 2.), Vec3::new(2., 1., 2.), Vec3::new(2., 1., -2.));
+        field.add_triangle(Vec3::new(-2., 0., 2.), Vec3::new(2., 1., -2.), Vec3::new(-2., 0., -2.));
+        let h = field.height(Vec3::new(0.25, 0.5, -0.25)).unwrap();
+        assert!((h - 0.5625).abs() < 1e-4, "{h}");
+        // too steep to stand on
+        let mut wall = HeightField::new(4., 0.5);
+        wall.add_triangle(Vec3::new(-2., 0., 0.), Vec3::new(2., 4., 0.), Vec3::new(2., 0., 0.5));
+        assert!(wall.layers.iter().all(|l| l.is_empty()));
+    }
+
+    #[test]
+    fn snaps_points_to_the_nearest_layer() {
+        let field = field();
+        assert_eq!(field.ground(Vec3::new(0., 0.8, 0.)), Vec3::new(0., 0., 0.));
+        assert_eq!(field.ground(Vec3::new(0., 2.2, 0.)), Vec3::new(0., 3., 0.));
+        // clearly in the air, or off the field
+        assert_eq!(field.ground(Vec3::new(0., 10., 0.)), Vec3::new(0., 10., 0.));
+        assert_eq!(field.ground(Vec3::new(10., 1., 0.)), Vec3::new(10., 1., 0.));
+    }
+
+    #[test]
+    fn platforms_follow_their_transform() {
+        let mut world = World::new();
+        world.insert_resource(HeightField::new(8., 0.5));
+        let mut meshes = Assets::<Mesh>::default();
+        let mesh = meshes.add(Plane3d::default().mesh().size(2., 2.).build());
+        world.insert_resource(meshes);
+        let e = world.spawn((Platform, mesh, GlobalTransform::from_xyz(-4., 2., 0.))).id();
+
+        world.run_system_once(platforms);
+        let field = world.resource::<HeightField>();
+        assert_eq!(field.height(Vec3::new(-4., 2., 0.)), Some(2.));
+        assert_eq!(field.height(Vec3::new(4., 2., 0.)), None);
+
+        // moved away, nothing is left behind
+        *world.get_mut::<GlobalTransform>(e).unwrap() = GlobalTransform::from_xyz(4., 1., 0.);
+        world.run_system_once(platforms);
+        let field = world.resource::<HeightField>();
+        assert_eq!(field.height(Vec3::new(-4., 2., 0.)), None);
+        assert_eq!(field.height(Vec3::new(4., 1., 0.)), Some(1.));
+    }
+
+    #[test]
+    fn bakes_nothing_without_ground() {
+        let mut world = World::new();
+        world.init_resource::<SceneSpawner>();
+        world.init_resource::<Assets<Mesh>>();
+        world.spawn((Baking, NotReady));
+        world.run_system_once(bake);
+        assert!(world.get_resource::<HeightField>().is_some());
+        assert!(world.query_filtered::<(), With<NotReady>>().iter(&world).next().is_none());
+    }
+
+    #[test]
+    fn paths_start_on_the_ground() {
+        let mut app = App::new();
+        app
+        .add_plugins((MinimalPlugins, ClockPlugin {seed: Some(0)}, MovementPlugin))
+        .init_resource::<MusicTimeline>()
+        .insert_resource(field())
+        ;
+        app.update();
+        let e = app.world_mut().spawn((Transform::from_xyz(-3., 0., 3.), GroundFollow)).id();
+        app.world_mut().commands().entity(e).start_path(
+            MovementPath::builder()
+            .to(Vec3::new(-3., 0.8, -3.), 1.)
+            .to(Vec3::new(3., 2.5, 0.), 1.)
+        );
+        app.world_mut().flush();
+
+        let mp = app.world().get::<MovementPath>(e).unwrap();
+        let points: Vec<Vec3> = mp.points().iter().map(|p| p.pos).collect();
+        assert_eq!(points, [Vec3::new(-3., 0., -3.), Vec3::new(3., 3., 0.)]);
+        // facing the snapped point, level
+        let forward = app.world().get::<Transform>(e).unwrap().forward();
+        assert!(forward.distance(Vec3::NEG_Z) < 1e-5, "{forward:?}");
+    }
+}
@@ -1,12 +1,12 @@
 use std::f32::consts::PI;
 
 use bevy::prelude::*;
-use crate::animator::{AllAnimations, AnimationKey, CurrentAnimation};
+use crate::animator::{AllAnimations, AnimationKey, Clip, CurrentAnimation};
 use crate::GameState;
 use crate::camera::{CameraSplitConf, ViewportGeom, CameraState};
 use crate::camera_target::{CameraTarget, SetCameraTarget};
 use crate::music::MusicEvent;
-use crate::movement::MovementPath;
+use crate::movement::{Easing, MovementCommandsExt, MovementPath, PathMode};
 
 // ---
 
@@ -35,6 +35,20 @@ pub struct RedGirl;
 #[derive(Component)]
 pub struct IntroMarker;
 
+// clips of director.glb and red-girl.glb, the files have no clip names, so they are
+// registered under the names the show uses them by, the staff clips are named the same in both
+
+const DIRECTOR_CLIPS: [(&str, usize); 4] = [("talk", 0), ("cue", 1), ("fly", 4), ("finish", 5)];
+const RED_GIRL_CLIPS: [(&str, usize); 3] = [("talk", 0), ("fly", 3), ("finish", 5)];
+
+pub const STAFF_TALK: &str = "talk";
+pub const STAFF_FLY: &str = "fly";
+pub const STAFF_FINISH: &str = "finish";
+const DIRECTOR_CUE: &str = "cue";
+
+const HOVER_DEPTH: f32 = 1.5;
+const HOVER_VELOCITY: f32 = 0.8;
+
 #[derive(Resource)]
 pub struct Dialogue(Vec<(bool, f32, AnimationKey, &'static str)>);
 
@@ -42,12 +56,13 @@ pub struct Dialogue(Vec<(bool, f32, AnimationKey, &'static str)>);
 
 fn load(
     mut cmd: Commands,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
     mut all_animations: ResMut<AllAnimations>,
     assets: ResMut<AssetServer>
 ) {
-    all_animations.add(AnimationKey::Director, "models/other/director.glb", 6, &mut graphs, &assets);
-    all_animations.add(AnimationKey::RedGirl, "models/other/red-girl.glb", 6, &mut graphs, &assets);
+    all_animations.add(AnimationKey::Director, "models/other/director.glb", Some(6), &assets);
+    all_animations.add(AnimationKey::RedGirl, "models/other/red-girl.glb", Some(6), &assets);
+    all_animations.alias(AnimationKey::Director, &DIRECTOR_CLIPS);
+    all_animations.alias(AnimationKey::RedGirl, &RED_GIRL_CLIPS);
 
     cmd.spawn((
         SceneBundle {
@@ -98,7 +113,7 @@ pub fn enter_intro(
             ), 
         ]) 
     {
-        ca.0 = 0;
+        ca.0 = Clip::Name(STAFF_TALK);
         *v = Visibility::Visible;
 
         csc.0.push(
@@ -210,10 +225,12 @@ fn enter_portal (
             0
         ));
         let dir = Dir3::new((Vec3::ZERO - t.translation).normalize()).unwrap();
-        cmd.entity(r_e).insert(
-            MovementPath::new(&vec! [
-                (t.translation.with_y(100.), 5., Some(3), Some(dir))
-            ], 0, Vec3::ZERO)
+        // up to the portal and hovering there, bobbing between the two points until the finish
+        cmd.entity(r_e).start_path(
+            MovementPath::builder()
+            .to(t.translation.with_y(100.), 5.).animation(STAFF_FLY).look_to(dir).easing(Easing::InOut)
+            .to(t.translation.with_y(100. - HOVER_DEPTH), HOVER_VELOCITY).look_to(dir).easing(Easing::InOut)
+            .mode(PathMode::PingPong)
         );        
     }
 }
@@ -235,7 +252,7 @@ fn music_event(
     if let Ok((dir_e, mut dir_t, mut ca)) =  dir_q.get_single_mut() {
         if count == 4 {
             dir_t.rotate_y(PI);
-            ca.0 = 1;
+            ca.0 = Clip::Name(DIRECTOR_CUE);
             cmd.trigger(SetCameraTarget(
                 CameraTarget::from_entity(dir_e).with_translation_bias(Vec3::new(1., 10., -8.)), 0)
             );     
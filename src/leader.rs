@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 use crate::
 {
-    animator::{AnimationKey, CurrentAnimation}, 
+    animator::{AnimationKey, Clip, CurrentAnimation}, 
     camera_target::{CameraTarget, SetCameraTarget}, 
-    intro::{Director, RedGirl}, 
-    movement::{MovementPath, MovementPathDone}, music::MusicEvent, GameState
+    girls::{GIRL_FLY, GIRL_LEADER_DANCE, GIRL_REST, GIRL_SPEAK}, 
+    intro::{Director, RedGirl, STAFF_FINISH, STAFF_FLY}, 
+    movement::{Easing, MovementCommandsExt, MovementPath, MovementPathDone}, music::MusicEvent, GameState
 };
 
 use crate::girls::GIRL_VELOCITY;
@@ -78,7 +79,7 @@ fn enter_portal(
     mut l_q: Query<(&mut CurrentAnimation, &mut Visibility), With<Leader>>,
 ) {
     if let Ok((mut ca, mut v)) = l_q.get_single_mut() {
-        ca.0 = 3;
+        ca.0 = Clip::Name(GIRL_LEADER_DANCE);
         *v = Visibility::Visible;
     }
 }
@@ -91,7 +92,7 @@ fn enter_speak(
     mut cmd: Commands
 ) {
     if let Ok((mut ca, te)) = l_q.get_single_mut() {
-        ca.0 = 0;
+        ca.0 = Clip::Name(GIRL_SPEAK);
         cmd.trigger(SetCameraTarget(CameraTarget::from_entity(te).with_translation_bias(Vec3::new(2., 5., 10.)), 0 ));
     }
     
@@ -204,17 +205,23 @@ fn enter_finish(
 
     let velo = GIRL_VELOCITY * 3.;
 
-    cmd.entity(de).insert(MovementPath::new(&vec![
-        (meet_point + Vec3::Z, velo, Some(4), None)
-    ], 5, -Vec3::X));
+    cmd.entity(de).start_path(MovementPath::builder()
+        .to(meet_point + Vec3::Z, velo).animation(STAFF_FLY).easing(Easing::InOut)
+        .finish_animation(STAFF_FINISH)
+        .finish_look_at(-Vec3::X)
+    );
 
-    cmd.entity(re).insert(MovementPath::new(&vec![
-        (meet_point + Vec3::X * 0.25, velo, Some(3), None)
-    ], 5, -Vec3::X));
+    cmd.entity(re).start_path(MovementPath::builder()
+        .to(meet_point + Vec3::X * 0.25, velo).animation(STAFF_FLY).easing(Easing::InOut)
+        .finish_animation(STAFF_FINISH)
+        .finish_look_at(-Vec3::X)
+    );
 
-    cmd.entity(le).insert(MovementPath::new(&vec![
-        (meet_point - Vec3::Z + Vec3::X * 0.25, velo, Some(12), None)
-    ], 9, -Vec3::X));
+    cmd.entity(le).start_path(MovementPath::builder()
+        .to(meet_point - Vec3::Z + Vec3::X * 0.25, velo).animation(GIRL_FLY).easing(Easing::InOut)
+        .finish_animation(GIRL_REST)
+        .finish_look_at(-Vec3::X)
+    );
 
     csc.0.push(
         (
@@ -1,13 +1,13 @@
 use bevy::prelude::* ;
-use crate::GameState;
+use crate::{clock::ShowUpdate, ground::{Ground, Platform}, nav::Obstacle, GameState};
 
 
 pub struct LiftPlugin;
 impl Plugin for LiftPlugin {
     fn build(&self, app: &mut App) {
         app
-        .add_systems(Startup, spawn)
-        .add_systems(Update, rotate.run_if(in_state(ScrewState::Moving)))
+        .add_systems(Startup, (setup, spawn))
+        .add_systems(ShowUpdate, rotate.run_if(in_state(ScrewState::Moving)))
         .add_systems(OnEnter(GameState::Portal), enter_portal)
         .register_type::<Screw>()
         .init_state::<ScrewState>()
@@ -32,8 +32,23 @@ pub enum ScrewState {
 }
 
 
+const LIFT_FOOTPRINT: Vec2 = Vec2::new(3., 3.);
+
 // --
 
+// the screw comes from the glTF extras, it turns, so the ground under it is not baked
+
+fn setup(
+    world: &mut World
+) {
+    world.register_component_hooks::<Screw>()
+    .on_add(|mut world, entity, _| {
+        world.commands().entity(entity).insert(Platform);
+    });
+}
+
+// ---
+
 fn spawn(
     mut commands: Commands, 
     assets: ResMut<AssetServer>
@@ -47,7 +62,9 @@ fn spawn(
                 transform: Transform::from_xyz(x * dist, 0., z * dist).looking_at(Vec3::ZERO, Vec3::Y),
                 ..default()
             },
-            Lift
+            Lift,
+            Ground,
+            Obstacle(LIFT_FOOTPRINT)
         ));
     }
 }
@@ -17,6 +17,16 @@ mod lift;
 mod music;
 mod intro;
 mod animator;
+mod steering;
+mod nav;
+mod clock;
+mod falling;
+mod formation;
+mod pairing;
+mod ground;
+#[cfg(feature = "debug_overlay")]
+mod debug;
+mod parachute;
 
 mod movement;
 
@@ -40,7 +50,8 @@ pub struct NotReady;
 // ---
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
     .insert_resource(ClearColor(Color::BLACK))
     .add_plugins((
         DefaultPlugins.set(
@@ -69,12 +80,24 @@ fn main() {
     .add_plugins((
         camera_target::CameraTargetPlugin, 
         intro::IntroPlugin,
-        animator::AnimatorPlugin
+        animator::AnimatorPlugin,
+        steering::SteeringPlugin,
+        nav::NavPlugin,
+        falling::FallingPlugin,
+        parachute::ParachutePlugin,
+        ground::GroundPlugin,
+        clock::ClockPlugin {
+            seed: std::env::var("SHOW_SEED").ok().and_then(|s| s.parse().ok())
+        }
     ))
     .init_state::<GameState>()
     .add_systems(Update, check_ready.run_if(in_state(GameState::Loading)))
-    .observe(the_end)
-    .run();
+    .observe(the_end);
+
+    #[cfg(feature = "debug_overlay")]
+    app.add_plugins(debug::DebugPlugin);
+
+    app.run();
 }
 
 // ---
@@ -1,13 +1,20 @@
 use bevy::prelude::*;
 use crate::{
-    animator::{AllAnimations, AnimationKey}, 
+    airplane::{ahead, Aboard, DropSchedule, ManDropped},
+    animator::{AllAnimations, AnimationKey, Clip, CurrentAnimation, Locomotion, Variety}, 
     camera::{Cam, CameraState}, 
     camera_target::{CameraTarget, SetCameraTarget}, 
-    girls::Girl, lift::Lift, 
-    GameState 
+    clock::{ShowTime, ShowUpdate},
+    falling::{Falling, Landed},
+    girls::{Girl, GIRL_DANCE, GIRL_FLY, GIRL_TURN_SPEED, GIRL_WALK}, ground::GroundFollow, lift::Lift, 
+    pairing::{pair_up, Unpaired},
+    parachute::Parachute,
+    steering::Separation, GameState 
 };
-use crate::shared::random_pos;
-use crate::movement:: {MovementPath, MovementPathDone, MovementStart};
+use crate::shared::ShowRng;
+use crate::movement:: {Dwell, MovementCommandsExt, MovementPath, MovementPathBuilder, MovementPathDone, MovementStart};
+use crate::music::MusicTimeline;
+use std::time::Duration;
 
 // ---
 
@@ -22,7 +29,7 @@ pub enum MenState {
 }
 
 #[derive(Component)]
-pub struct Pair(Entity);
+pub struct Pair(pub Entity);
 
 #[derive(Component)]
 pub struct Paired;
@@ -34,15 +41,42 @@ impl Plugin for MenPlugin {
         app
         .add_systems(Startup, spawn)
         .add_systems(OnEnter(CameraState::Single), enter_moving.run_if(in_state(GameState::Raining)))
-        .add_systems(Update, cam_rotate.run_if(in_state(MenState::Moving)))
+        .add_systems(ShowUpdate, cam_rotate.run_if(in_state(MenState::Moving)))
         .add_systems(Update, move_done.run_if(on_event::<MovementPathDone>()))
+        .add_systems(Update, dropped.run_if(on_event::<ManDropped>()))
+        .add_systems(Update, landed.run_if(on_event::<Landed>()))
         .init_state::<MenState>()
         ;
     }
 } 
 
+// clips of peasant-man.glb, "wailk" is spelled as in the file
+
+const DANCE: &str = "dancing";
+const FALL: &str = "falling";
+const IDLE: &str = "idle";
+const RUN: &str = "running";
+const WALK: &str = "wailk";
+
+pub const FOOTSTEP: &str = "footstep";
+// the dance layer on the way to the girl, the legs keep running under it
+const UPPER_BODY: &str = "mixamorig:Spine1";
+
 const MEN_COUNT : usize = 64;
 const MAN_VELOCITY: f32 = 2.;
+const LIFT_DWELL: f32 = 0.5;
+const DEPARTURE_BEATS: u32 = 2;
+const MAN_TURN_SPEED: f32 = 6.;
+// getting up to a run after the landing
+const MAN_ACCELERATION: f32 = 3.;
+const MAN_RADIUS: f32 = 0.4;
+const MAN_WALK_STRIDE: f32 = 2.;
+const MAN_RUN_STRIDE: f32 = 5.;
+const MAN_SPEED_JITTER: f32 = 0.1;
+const LANDING_TIME: f32 = 1.;
+const MAX_TUMBLE: f32 = 3.;
+const DEPLOY_ALTITUDE: f32 = 40.;
+const DEPLOY_SPREAD: f32 = 10.;
 
 // ---
 
@@ -50,23 +84,33 @@ fn spawn (
     mut commands: Commands,
     assets: ResMut<AssetServer>,
     mut all_animations: ResMut<AllAnimations>,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
-    
 ) {
 
-    all_animations.add(AnimationKey::Man, "models/men/peasant-man.glb", 5, &mut graphs, &assets);
+    all_animations.add(AnimationKey::Man, "models/men/peasant-man.glb", Some(5), &assets);
+    all_animations.set_locomotion(AnimationKey::Man, Locomotion {
+        walk: Clip::Name(WALK), walk_speed: MAN_WALK_STRIDE,
+        run: Clip::Name(RUN), run_speed: MAN_RUN_STRIDE
+    });
+    // heel strikes, just before the hips are at their lowest
+    all_animations.add_marker(AnimationKey::Man, WALK, 0.55, FOOTSTEP);
+    all_animations.add_marker(AnimationKey::Man, WALK, 1.25, FOOTSTEP);
+    all_animations.set_variety(AnimationKey::Man, Variety {random_phase: true, speed_jitter: MAN_SPEED_JITTER});
+    all_animations.set_blend(AnimationKey::Man, FALL, IDLE, Duration::from_millis(600));
+    all_animations.set_blend(AnimationKey::Man, RUN, DANCE, Duration::from_millis(500));
     let gh = assets.load("models/men/peasant-man.glb#Scene0");
 
     for _j in 0 .. MEN_COUNT {
         commands.spawn((
             SceneBundle {
                 scene: gh.clone(),
-                transform: Transform::from_translation(random_pos(Vec3::new(0., 50., 0.), 5.)),
                 visibility: Visibility::Hidden,
                 ..default()
             },
             Man,
-            AnimationKey::Man
+            Aboard,
+            GroundFollow,
+            AnimationKey::Man,
+            Separation::new(MAN_RADIUS, 0.5)
         ));
     }        
 }
@@ -74,25 +118,10 @@ fn spawn (
 // ---
 
 fn enter_moving(
-    mut men_q: Query<(&mut Visibility, Entity), With<Man>>,
-    girls_q: Query<(&Transform, Entity), (With<Girl>, Without<Man>)>,
     mut ccc: Commands,
     cam_q: Query<Entity, With<Cam>>,
     mut next: ResMut<NextState<MenState>>,
 ) {
-    for ((mut vis , man_entity), (gt, ge)) in men_q.iter_mut().zip(girls_q.iter()) {
-        *vis = Visibility::Visible;
-        let velocity = fastrand::f32() + MAN_VELOCITY;
-        let points = vec![
-            (gt.translation - gt.forward() * 10. , velocity * 2., Some(1), None),
-            (gt.translation + gt.right() * 1. , velocity * 2. , Some(3), None)
-        ];
-
-        ccc.entity(man_entity).insert(
-            MovementPath::new(&points, 0, gt.translation)
-        );
-        ccc.entity(man_entity).insert(Pair(ge));
-    }
     if let Ok(cam_e) = cam_q.get_single() {
         ccc.entity(cam_e).insert(CameraTarget::from_position(Vec3::ZERO.with_y(20.)).with_direction(Dir3::Y));
     }
@@ -101,22 +130,127 @@ fn enter_moving(
 
 // ---
 
+// the first batch leaving the plane pairs everyone, with one assignment over all men and girls.
+// The men still aboard are placed where the plane will be when their turn comes,
+// the parachutes glide everyone to their girl from there. Extras on either side are marked
+
+fn dropped(
+    mut dropped_r: EventReader<ManDropped>,
+    mut men_q: Query<(&Transform, &mut CurrentAnimation), With<Man>>,
+    girls_q: Query<(Entity, &Transform), With<Girl>>,
+    aboard_q: Query<Entity, (With<Man>, With<Aboard>)>,
+    schedule: Res<DropSchedule>,
+    mut rng: ResMut<ShowRng>,
+    mut cmd: Commands,
+    mut first_drop: Local<bool>
+) {
+    let mut batch = Vec::new();
+    let mut plane = None;
+    for ManDropped(man_entity, plane_velocity) in dropped_r.read() {
+        let Ok((t, mut ca)) = men_q.get_mut(*man_entity) else {
+            continue;
+        };
+        // out of the hatch at once, no stepping into the fall
+        ca.crossfade(FALL, Duration::ZERO);
+        let push = Vec3::new(rng.0.f32() - 0.5, 0., rng.0.f32() - 0.5);
+        let spin = Vec3::new(rng.0.f32() - 0.5, rng.0.f32() - 0.5, rng.0.f32() - 0.5) * 2. * MAX_TUMBLE;
+        cmd.entity(*man_entity).insert((
+            Falling::new(*plane_velocity + push, spin),
+            Parachute::new(DEPLOY_ALTITUDE + rng.0.f32() * DEPLOY_SPREAD).with_animation(IDLE)
+        ));
+        batch.push((*man_entity, t.translation));
+        plane = Some((t.translation, *plane_velocity));
+    }
+    let Some((hatch, velocity)) = plane else {
+        return;
+    };
+    if *first_drop {
+        return;
+    }
+    *first_drop = true;
+
+    // the camera turns from the sky to the plane when the first one jumps
+    let eye = Vec3::ZERO.with_y(20.);
+    cmd.trigger(SetCameraTarget(
+        CameraTarget::from_position(eye).with_rotation_bias(hatch - eye), 0
+    ));
+
+    // aboard in the order the hatch lets them go
+    let men: Vec<(Entity, Vec3)> = batch.into_iter()
+        .chain(aboard_q.iter().enumerate().map(|(k, e)| (e, ahead(hatch, velocity, schedule.wait(k)))))
+        .collect();
+    let girls: Vec<(Entity, Vec3)> = girls_q.iter().map(|(ge, gt)| (ge, gt.translation)).collect();
+    let (pairs, extras) = pair_up(&men, &girls);
+    for (man_entity, ge) in pairs {
+        cmd.entity(man_entity).insert(Pair(ge));
+    }
+    for e in extras {
+        cmd.entity(e).insert(Unpaired);
+    }
+}
+
+// ---
+
+fn landed(
+    mut landed_r: EventReader<Landed>,
+    mut men_q: Query<(&Transform, Option<&Pair>, &mut CurrentAnimation), With<Man>>,
+    girls_q: Query<&Transform, (With<Girl>, Without<Man>)>,
+    mut rng: ResMut<ShowRng>,
+    mut cmd: Commands
+) {
+    for e in landed_r.read() {
+        let Ok((t, pair, mut ca)) = men_q.get_mut(e.0) else {
+            continue;
+        };
+        // nobody left for him, a dance on his own and he stays where he landed
+        let Some(gt) = pair.and_then(|pair| girls_q.get(pair.0).ok()) else {
+            ca.0 = Clip::Name(IDLE);
+            ca.play_once(DANCE, true);
+            continue;
+        };
+        ca.set_layer(DANCE, UPPER_BODY);
+        let velocity = rng.0.f32() + MAN_VELOCITY;
+        cmd.entity(e.0).start_path(
+            MovementPath::builder()
+            .to(t.translation, velocity).animation(IDLE)
+            .dwell(Dwell {duration: LANDING_TIME, animation: Some(Clip::Name(IDLE)), look_to: None})
+            .to(gt.translation - gt.forward() * 10., velocity * 2.).animation(RUN).max_acceleration(MAN_ACCELERATION)
+            .to(gt.translation + gt.right() * 1., velocity * 2.).animation(RUN)
+            .turn_speed(MAN_TURN_SPEED)
+            .finish_animation(DANCE)
+            .finish_look_at(gt.translation)
+        );
+    }
+}
+
+// ---
+
 fn move_done(
     mut ev_r: EventReader<MovementPathDone>,
     man_q: Query<(Entity, &Pair, Option<&Paired>, &Transform), (With<Man>, Without<Lift>)>,
+    mut animations_q: Query<&mut CurrentAnimation, With<Man>>,
     girl_q: Query<Entity, With<Girl>>,
     lifts_q: Query<&Transform, (With<Lift>, Without<Man>)>,
     mut next: ResMut<NextState<GameState>>,
     mut cmd: Commands,
     mut first_man_set : Local<bool>,
     mut count: Local<usize>,
-    time: Res<Time>
+    mut rng: ResMut<ShowRng>,
+    timeline: Res<MusicTimeline>,
+    time: ShowTime
 ) {
+    let time = time.now();
     for e in ev_r.read() {
         if let Ok((entity_m, pair, op, man_t)) = man_q.get(e.0) {
             if op.is_some() {
                 continue;
             }
+            // with the girl, dancing all over
+            if let Ok(mut ca) = animations_q.get_mut(entity_m) {
+                if ca.layer().is_some() {
+                    ca.clear_layer();
+                }
+            }
             if ! *first_man_set {
                 *first_man_set = true;
                 cmd.trigger(SetCameraTarget(
@@ -138,32 +272,53 @@ fn move_done(
             let p0 = 25.5 * dir;
 
             let p1 = (p0 + 16. * dir).with_y(7.);
-            let bias = fastrand::f32() * 2.;
+            let bias = rng.0.f32() * 2.;
             let p2 = (p1 + 2.0 * dir * (1. + bias)).with_y(6.5);
             let p3 = p2.with_y(200.0);
 
             let last_dir = Dir3::new((Vec3::ZERO - p0).normalize()).unwrap();
 
-            let points_m = vec![
-                (p0, MAN_VELOCITY, Some(4), None),
-                (p1, MAN_VELOCITY, None, None),
-                (p2, MAN_VELOCITY, None, None),
-                (p3, MAN_VELOCITY, Some(1), Some(-last_dir)),
-            ];
-            cmd.entity(entity_m).insert(MovementPath::new(&points_m, 2 , Vec3::X));
+            // pairs leave on beats and step onto the lift platform on a beat
+            let (start_time, platform_beat) = match timeline.position(&time) {
+                Some(song) => {
+                    let depart = timeline.beat_time(timeline.beat_at(song).ceil() as u32 + *count as u32 * DEPARTURE_BEATS);
+                    let travel = (man_t.translation.distance(p0) + p0.distance(p1) + p1.distance(p2)) / MAN_VELOCITY;
+                    (time.elapsed_seconds() + depart - song, Some(timeline.beat_at(depart + travel).ceil() as u32))
+                },
+                None => (time.elapsed_seconds() + *count  as f32 * 1.0, None)
+            };
+            let sync = |path: MovementPathBuilder| match platform_beat {
+                Some(b) => path.arrive_on_beat(b),
+                None => path
+            };
+
+            cmd.entity(entity_m).start_path(
+                sync(MovementPath::builder()
+                .to(p0, MAN_VELOCITY).animation(WALK)
+                .to(p1, MAN_VELOCITY)
+                .to(p2, MAN_VELOCITY))
+                .dwell(Dwell {duration: LIFT_DWELL, animation: Some(Clip::Name(FALL)), look_to: Some(-last_dir)})
+                .to(p3, MAN_VELOCITY).animation(FALL).look_to(-last_dir)
+                .turn_speed(MAN_TURN_SPEED)
+                .finish_animation(IDLE)
+                .finish_look_at(Vec3::X)
+            );
             cmd.entity(entity_m).insert(Paired);
-            let start_time = time.elapsed_seconds() + *count  as f32 * 1.0;
             cmd.entity(entity_m).insert(MovementStart(start_time));
 
             if let Ok(entity_g) =  girl_q.get(pair.0) {
                 let shift = dir.cross(Vec3::Y) * 2.0 *  (if *count % 2 == 0 {-1.} else {1.});
-                let points_g = vec![
-                    (p0 + shift, MAN_VELOCITY, Some(5), None),
-                    (p1 + shift, MAN_VELOCITY, None, None),
-                    (p2 + shift, MAN_VELOCITY, None, None),
-                    (p3 + shift, MAN_VELOCITY, Some(12), Some(last_dir))
-                ];
-                cmd.entity(entity_g).insert(MovementPath::new(&points_g, 2 , Vec3::ZERO));
+                cmd.entity(entity_g).start_path(
+                    sync(MovementPath::builder()
+                    .to(p0 + shift, MAN_VELOCITY).animation(GIRL_WALK)
+                    .to(p1 + shift, MAN_VELOCITY)
+                    .to(p2 + shift, MAN_VELOCITY))
+                    .dwell(Dwell {duration: LIFT_DWELL, animation: Some(Clip::Name(GIRL_FLY)), look_to: Some(last_dir)})
+                    .to(p3 + shift, MAN_VELOCITY).animation(GIRL_FLY).look_to(last_dir)
+                    .turn_speed(GIRL_TURN_SPEED)
+                    .finish_animation(GIRL_DANCE)
+                    .finish_look_at(Vec3::ZERO)
+                );
                 cmd.entity(entity_g).insert(MovementStart(start_time));
 
             }
@@ -1,12 +1,23 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::EntityCommands, prelude::*};
 
-use crate::animator::CurrentAnimation;
+use crate::{
+    animator::{Clip, CurrentAnimation},
+    clock::ShowUpdate,
+    ground::{GroundFollow, HeightField},
+    music::MusicTimeline,
+    steering::Separation
+};
 pub struct MovementPlugin;
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_systems(Startup, setup)
-        .add_systems(Update, do_moving.run_if(any_with_component::<MovementPath>))
+        .add_systems(ShowUpdate, do_moving.run_if(any_with_component::<MovementPath>))
+        .add_systems(ShowUpdate, do_turning.run_if(any_with_component::<Turning>))
+        .add_event::<WaypointReached>()
+        .add_event::<MovementPathDone>()
+        .add_event::<MovementPathCancelled>()
+        .add_event::<MovementCycleDone>()
         ;
     }
 }
@@ -15,43 +26,504 @@ impl Plugin for MovementPlugin {
 #[derive(Component)]
 pub struct MovementStart(pub f32);
 
-#[derive(Clone, Copy)]
+#[derive(Component)]
+pub struct MovementPaused;
+
+// the final turn towards finish_look_at, which outlives the path itself
+
+#[derive(Component)]
+pub struct Turning {
+    pub target: Quat,
+    pub speed: f32
+}
+
+#[derive(Clone, Copy, Default)]
 pub struct PathElement {
     pub pos: Vec3,
     pub velocity: f32,
-    pub animation_index: Option<usize>,
+    pub animation: Option<Clip>,
+    pub look_to: Option<Dir3>,
+    pub easing: Easing,
+    pub max_acceleration: Option<f32>,
+    pub dwell: Option<Dwell>,
+    pub arrive: Option<Arrival>
+}
+
+// arrival in song time, the speed is derived from the remaining distance,
+// velocity is used while the music is not playing
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Arrival {
+    At(f32),
+    Beat(u32)
+}
+
+#[derive(Clone, Copy)]
+pub struct Dwell {
+    pub duration: f32,
+    pub animation: Option<Clip>,
     pub look_to: Option<Dir3>
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Easing {
+    #[default]
+    None,
+    In,
+    Out,
+    InOut
+}
+
+impl Easing {
+    fn eases_in(&self) -> bool {
+        matches!(self, Easing::In | Easing::InOut)
+    }
+
+    fn eases_out(&self) -> bool {
+        matches!(self, Easing::Out | Easing::InOut)
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum PathInterpolation {
+    #[default]
+    Linear,
+    CatmullRom
+}
+
+// Once consumes the path and removes it, Loop and PingPong keep going until cancelled
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum PathMode {
+    #[default]
+    Once,
+    Loop,
+    PingPong
+}
+
+#[derive(Component, Clone)]
 pub struct MovementPath {
-    pub points: Vec<PathElement>,
-    pub finish_animation: usize,
-    pub finish_look_at: Vec3
+    points: Vec<PathElement>,
+    finish_animation: Option<Clip>,
+    finish_look_at: Option<Vec3>,
+    interpolation: PathInterpolation,
+    mode: PathMode,
+    turn_speed: Option<f32>,
+    facing: Option<Quat>,
+    speed: f32,
+    cursor: usize,
+    forward: bool,
+    cycles: usize,
+    completed: bool,
+    dwell_left: Option<f32>,
+    prev: Option<Vec3>,
+    segment: Option<PathSegment>,
+    offset: Vec3
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MovementPathError {
+    Empty,
+    StepBeforePoint,
+    InvalidPosition(usize),
+    InvalidVelocity(usize),
+    InvalidDwell(usize),
+    InvalidArrival(usize),
+    InvalidTurnSpeed,
+    TooShortForCycle
+}
+
+impl std::fmt::Display for MovementPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovementPathError::Empty => write!(f, "movement path has no points"),
+            MovementPathError::StepBeforePoint => write!(f, "point modifier used before the first point"),
+            MovementPathError::InvalidPosition(i) => write!(f, "point {i} has a non finite position"),
+            MovementPathError::InvalidVelocity(i) => write!(f, "point {i} has a non positive velocity"),
+            MovementPathError::InvalidDwell(i) => write!(f, "point {i} has a negative dwell duration"),
+            MovementPathError::InvalidArrival(i) => write!(f, "point {i} has a non finite arrival time"),
+            MovementPathError::InvalidTurnSpeed => write!(f, "turn speed must be positive"),
+            MovementPathError::TooShortForCycle => write!(f, "looping paths need at least two points")
+        }
+    }
+}
+
+impl std::error::Error for MovementPathError {}
+
+// ---
+
+// point modifiers (animation, look_to, easing ...) apply to the last added point
+
+#[derive(Default)]
+pub struct MovementPathBuilder {
+    points: Vec<PathElement>,
+    finish_animation: Option<Clip>,
+    finish_look_at: Option<Vec3>,
+    interpolation: PathInterpolation,
+    mode: PathMode,
+    turn_speed: Option<f32>,
+    error: Option<MovementPathError>
+}
+
+impl MovementPathBuilder {
+    pub fn to(mut self, pos: Vec3, velocity: f32) -> Self {
+        self.points.push(PathElement {
+            pos,
+            velocity,
+            ..default()
+        });
+        self
+    }
+
+    fn modify(mut self, f: impl FnOnce(&mut PathElement)) -> Self {
+        match self.points.last_mut() {
+            Some(pe) => f(pe),
+            None => {
+                self.error.get_or_insert(MovementPathError::StepBeforePoint);
+            }
+        }
+        self
+    }
+
+    pub fn animation(self, clip: impl Into<Clip>) -> Self {
+        self.modify(|pe| pe.animation = Some(clip.into()))
+    }
+
+    pub fn look_to(self, d: Dir3) -> Self {
+        self.modify(|pe| pe.look_to = Some(d))
+    }
+
+    pub fn easing(self, e: Easing) -> Self {
+        self.modify(|pe| pe.easing = e)
+    }
+
+    pub fn max_acceleration(self, a: f32) -> Self {
+        self.modify(|pe| pe.max_acceleration = Some(a))
+    }
+
+    pub fn dwell(self, d: Dwell) -> Self {
+        self.modify(|pe| pe.dwell = Some(d))
+    }
+
+    pub fn arrive_at(self, t: f32) -> Self {
+        self.modify(|pe| pe.arrive = Some(Arrival::At(t)))
+    }
+
+    pub fn arrive_on_beat(self, beat: u32) -> Self {
+        self.modify(|pe| pe.arrive = Some(Arrival::Beat(beat)))
+    }
+
+    pub fn interpolation(mut self, i: PathInterpolation) -> Self {
+        self.interpolation = i;
+        self
+    }
+
+    pub fn mode(mut self, m: PathMode) -> Self {
+        self.mode = m;
+        self
+    }
+
+    // radians per second, without it the facing snaps
+
+    pub fn turn_speed(mut self, s: f32) -> Self {
+        self.turn_speed = Some(s);
+        self
+    }
+
+    pub fn finish_animation(mut self, clip: impl Into<Clip>) -> Self {
+        self.finish_animation = Some(clip.into());
+        self
+    }
+
+    pub fn finish_look_at(mut self, p: Vec3) -> Self {
+        self.finish_look_at = Some(p);
+        self
+    }
+
+    pub fn build(self) -> Result<MovementPath, MovementPathError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.points.is_empty() {
+            return Err(MovementPathError::Empty);
+        }
+        if self.mode != PathMode::Once && self.points.len() < 2 {
+            return Err(MovementPathError::TooShortForCycle);
+        }
+        if self.turn_speed.is_some_and(|s| s <= 0. || !s.is_finite()) {
+            return Err(MovementPathError::InvalidTurnSpeed);
+        }
+        for (i, pe) in self.points.iter().enumerate() {
+            if !pe.pos.is_finite() {
+                return Err(MovementPathError::InvalidPosition(i));
+            }
+            if pe.velocity <= 0. || !pe.velocity.is_finite() {
+                return Err(MovementPathError::InvalidVelocity(i));
+            }
+            if pe.dwell.is_some_and(|d| d.duration < 0. || d.duration.is_nan()) {
+                return Err(MovementPathError::InvalidDwell(i));
+            }
+            if matches!(pe.arrive, Some(Arrival::At(t)) if !t.is_finite()) {
+                return Err(MovementPathError::InvalidArrival(i));
+            }
+        }
+
+        Ok(MovementPath {
+            points: self.points,
+            finish_animation: self.finish_animation,
+            finish_look_at: self.finish_look_at,
+            interpolation: self.interpolation,
+            mode: self.mode,
+            turn_speed: self.turn_speed,
+            facing: None,
+            speed: 0.,
+            cursor: 0,
+            forward: true,
+            cycles: 0,
+            completed: false,
+            dwell_left: None,
+            prev: None,
+            segment: None,
+            offset: Vec3::ZERO
+        })
+    }
 }
 
+// ---
+
 impl MovementPath {
-    pub fn new(points: &Vec<(Vec3, f32, Option<usize>, Option<Dir3>)>, finish_animation: usize, finish_look_at: Vec3 ) -> Self {
-        MovementPath {
-            points: points.iter().map(|(p, v, a, l)| {
-                    PathElement {
-                        pos: *p,
-                        velocity: *v, 
-                        animation_index: *a,
-                        look_to: *l
-                    }
-                    }).collect(),
-            finish_animation,
-            finish_look_at        
+    pub fn builder() -> MovementPathBuilder {
+        MovementPathBuilder::default()
+    }
+
+    #[cfg_attr(not(feature = "debug_overlay"), allow(dead_code))]
+    pub fn points(&self) -> &[PathElement] {
+        &self.points
+    }
+
+    fn map_points(&mut self, f: impl Fn(Vec3) -> Vec3) {
+        for p in self.points.iter_mut() {
+            p.pos = f(p.pos);
         }
+        self.finish_look_at = self.finish_look_at.map(&f);
+    }
 
+    fn face(&mut self, t: &mut Transform, target: Quat) {
+        if self.turn_speed.is_some() {
+            self.facing = Some(target);
+        } else {
+            t.rotation = target;
+        }
+    }
+
+    fn turn(&mut self, t: &mut Transform, dt: f32) {
+        if let (Some(target), Some(speed)) = (self.facing, self.turn_speed) {
+            t.rotation = turn_towards(t.rotation, target, speed * dt);
+            if t.rotation == target {
+                self.facing = None;
+            }
+        }
+    }
+
+    pub fn current(&self) -> &PathElement {
+        &self.points[self.cursor]
+    }
+
+    // how fast the walker at `pos` goes over the ground, nothing while dwelling,
+    // heading up or down (a lift ride) only the horizontal part counts
+
+    pub fn ground_speed(&self, pos: Vec3) -> f32 {
+        if self.dwell_left.is_some() {
+            return 0.;
+        }
+        let dir = self.current().pos - pos;
+        let length = dir.length();
+        if length <= f32::EPSILON {
+            return 0.;
+        }
+        self.speed * dir.xz().length() / length
+    }
+
+    // next cursor position and direction, None when a Once path is over
+
+    fn following(&self) -> Option<(usize, bool)> {
+        let last = self.points.len() - 1;
+        let c = self.cursor;
+        match self.mode {
+            PathMode::Once => (c < last).then_some((c + 1, true)),
+            PathMode::Loop => Some(((c + 1) % self.points.len(), true)),
+            PathMode::PingPong => Some(
+                if last == 0 {
+                    (0, true)
+                } else if self.forward {
+                    if c < last {(c + 1, true)} else {(c - 1, false)}
+                } else if c > 0 {
+                    (c - 1, false)
+                } else {
+                    (c + 1, true)
+                }
+            )
+        }
+    }
+
+    // without easing and acceleration limit the element velocity is applied instantly, as before
+
+    fn next_speed(&mut self, remaining: f32, dt: f32, timeline: &MusicTimeline, time: &Time) -> f32 {
+        let pe = *self.current();
+        if let (Some(arrive), Some(now)) = (pe.arrive, timeline.position(time)) {
+            let at = match arrive {
+                Arrival::At(t) => t,
+                Arrival::Beat(b) => timeline.beat_time(b)
+            };
+            self.speed = (remaining / (at - now).max(dt)).min(pe.velocity * MAX_CATCH_UP);
+            return self.speed;
+        }
+        if pe.easing == Easing::None && pe.max_acceleration.is_none() {
+            self.speed = pe.velocity;
+            return self.speed;
+        }
+        let accel = pe.max_acceleration.unwrap_or(DEFAULT_ACCELERATION);
+        let mut target = pe.velocity;
+        if pe.easing.eases_out() {
+            target = target.min((2. * accel * remaining).sqrt()).max(MIN_EASED_SPEED);
+        }
+        self.speed = if pe.easing.eases_in() || pe.max_acceleration.is_some() {
+            if self.speed < target {
+                (self.speed + accel * dt).min(target)
+            } else {
+                target
+            }
+        } else {
+            target
+        };
+        self.speed
+    }
+
+    fn build_segment(&self, from: Vec3) -> PathSegment {
+        let to = self.current().pos;
+        let before = self.prev.unwrap_or(from - (to - from));
+        let after = self.following().map(|(i, _)| self.points[i].pos).unwrap_or(to + (to - from));
+        PathSegment::catmull_rom(before, from, to, after)
+    }
+}
+
+// ---
+
+const SEGMENT_SUBDIVISIONS: usize = 16;
+const DEFAULT_ACCELERATION: f32 = 4.;
+const MIN_EASED_SPEED: f32 = 0.2;
+const STEERING_FADE: f32 = 1.;
+const OFFSET_RECOVERY: f32 = 1.;
+// a late walker hurries, but not faster than this many times its own velocity
+const MAX_CATCH_UP: f32 = 3.;
+
+fn turn_towards(from: Quat, to: Quat, max_angle: f32) -> Quat {
+    let angle = from.angle_between(to);
+    if angle <= max_angle {
+        to
+    } else {
+        from.slerp(to, max_angle / angle)
+    }
+}
+
+// arc-length table of a single curve piece, so that velocity is measured along the curve
+
+#[derive(Clone)]
+struct PathSegment {
+    samples: Vec<Vec3>,
+    lengths: Vec<f32>,
+    travelled: f32
+}
+
+impl PathSegment {
+    fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3) -> Self {
+        // the spline runs through all four points, p0 and p3 only shape the piece from p1 to p2
+        let curve = CubicCardinalSpline::new_catmull_rom([p0, p1, p2, p3]).to_curve();
+        let segment = curve.segments()[1];
+        let samples: Vec<Vec3> = (0 ..= SEGMENT_SUBDIVISIONS)
+            .map(|i| segment.position(i as f32 / SEGMENT_SUBDIVISIONS as f32))
+            .collect();
+        let mut lengths = Vec::with_capacity(samples.len());
+        let mut total = 0.;
+        lengths.push(total);
+        for w in samples.windows(2) {
+            total += w[0].distance(w[1]);
+            lengths.push(total);
+        }
+        PathSegment {
+            samples,
+            lengths,
+            travelled: 0.
+        }
+    }
+
+    fn length(&self) -> f32 {
+        *self.lengths.last().unwrap()
+    }
+
+    fn sample(&self, d: f32) -> (Vec3, Vec3) {
+        let i = self.lengths.partition_point(|l| *l < d).clamp(1, self.samples.len() - 1);
+        let (a, b) = (self.samples[i - 1], self.samples[i]);
+        let span = self.lengths[i] - self.lengths[i - 1];
+        let k = if span > 0. {((d - self.lengths[i - 1]) / span).clamp(0., 1.)} else {1.};
+        (a.lerp(b, k), b - a)
     }
 }
 
 // ---
 
+// index is the position of the waypoint in the path as it was inserted
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct WaypointReached(pub Entity, pub usize);
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct MovementPathDone(pub Entity, pub usize);
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct MovementPathCancelled(pub Entity, pub usize);
+
+// number of cycles completed so far by a Loop or PingPong path
+
 #[derive(Event)]
-pub struct MovementPathDone(pub Entity);
+#[allow(dead_code)]
+pub struct MovementCycleDone(pub Entity, pub usize);
+
+// ---
+
+pub trait MovementCommandsExt {
+    fn start_path(&mut self, path: MovementPathBuilder) -> &mut Self;
+    fn pause_movement(&mut self) -> &mut Self;
+    fn resume_movement(&mut self) -> &mut Self;
+    fn cancel_movement(&mut self) -> &mut Self;
+}
+
+impl MovementCommandsExt for EntityCommands<'_> {
+    fn start_path(&mut self, path: MovementPathBuilder) -> &mut Self {
+        match path.build() {
+            Ok(mp) => {
+                self.insert(mp);
+            },
+            Err(e) => error!("{:?}: {}", self.id(), e)
+        }
+        self
+    }
+
+    fn pause_movement(&mut self) -> &mut Self {
+        self.insert(MovementPaused)
+    }
+
+    fn resume_movement(&mut self) -> &mut Self {
+        self.remove::<MovementPaused>()
+    }
+
+    fn cancel_movement(&mut self) -> &mut Self {
+        self.remove::<(MovementPath, MovementPaused, MovementStart)>()
+    }
+}
 
 // ---
 
@@ -60,15 +532,49 @@ fn setup(
 ) {
     world.register_component_hooks::<MovementPath>()
     .on_insert(|mut world, entity, _| {
-        let mp0 = world.get::<MovementPath>(entity).unwrap().points[0];
-        let idx = mp0.animation_index.unwrap();
-        let mut ca = world.get_mut::<CurrentAnimation>(entity).unwrap();
-        ca.0 = idx;
-        let mut t =  world.get_mut::<Transform>(entity).unwrap();
-        t.look_at(mp0.pos, Vec3::Y);
+        // a walker's points are put on the ground first, so it faces where it will really go
+        if world.get::<GroundFollow>(entity).is_some() {
+            let snapped = world.get_resource::<HeightField>()
+                .zip(world.get::<MovementPath>(entity))
+                .map(|(field, mp)| {
+                    let mut mp = mp.clone();
+                    mp.map_points(|p| field.ground(p));
+                    mp
+                });
+            if let Some(snapped) = snapped {
+                *world.get_mut::<MovementPath>(entity).unwrap() = snapped;
+            }
+        }
+        let Some(mp0) = world.get::<MovementPath>(entity).and_then(|mp| mp.points.get(mp.cursor)).copied() else {
+            return;
+        };
+        if let (Some(idx), Some(mut ca)) = (mp0.animation, world.get_mut::<CurrentAnimation>(entity)) {
+            ca.0 = idx;
+        }
+        world.commands().entity(entity).remove::<Turning>();
+        let Some(t) = world.get::<Transform>(entity).copied() else {
+            return;
+        };
+        let Some(target) = facing(&t, &mp0) else {
+            return;
+        };
+        let mut mp = world.get_mut::<MovementPath>(entity).unwrap();
+        if mp.turn_speed.is_some() {
+            mp.facing = Some(target);
+        } else {
+            world.get_mut::<Transform>(entity).unwrap().rotation = target;
+        }
     })
     .on_remove(|mut world, entity, _| {
-        world.send_event(MovementPathDone(entity));
+        let Some(mp) = world.get::<MovementPath>(entity) else {
+            return;
+        };
+        let (completed, cursor) = (mp.completed, mp.cursor);
+        if completed {
+            world.send_event(MovementPathDone(entity, cursor));
+        } else {
+            world.send_event(MovementPathCancelled(entity, cursor));
+        }
     })
     ;
 }
@@ -76,57 +582,481 @@ fn setup(
 // ---
 
 pub fn do_moving (
-    mut objects_q: Query<(&mut Transform, &mut MovementPath, &mut CurrentAnimation, Option<&MovementStart>, Entity), With<MovementPath>>,
+    mut objects_q: Query<(&mut Transform, &mut MovementPath, Option<&mut CurrentAnimation>, Option<&MovementStart>, Option<&Separation>, Entity), Without<MovementPaused>>,
     mut commands: Commands,
+    mut reached_w: EventWriter<WaypointReached>,
+    mut cycle_w: EventWriter<MovementCycleDone>,
+    timeline: Res<MusicTimeline>,
     time: Res<Time>,
 ) {
-    for (mut t, mut mp, mut ca, ms, entity) in objects_q.iter_mut() {
+    for (mut t, mut mp, mut ca, ms, sep, entity) in objects_q.iter_mut() {
         if let Some(start) = ms {
             if start.0 > time.elapsed_seconds() {
                 continue;
             }
             commands.entity(entity).remove::<MovementStart>();
         } 
-        let delta = mp.points[0].pos - t.translation;
-        let step = time.delta_seconds() * mp.points[0].velocity;
-
-        if delta.length_squared() < step * step {
-            t.translation = mp.points[0].pos;
-            commands.trigger(MovementPathDone(entity));
-            mp.points.remove(0);
-            
-            if mp.points.len() == 0 {
-                ca.0 = mp.finish_animation;
-                commands.entity(entity).remove::<MovementPath>();
-                
-                t.look_at(mp.finish_look_at, Vec3::Y);
+        mp.turn(&mut t, time.delta_seconds());
+        if let Some(left) = mp.dwell_left {
+            let left = left - time.delta_seconds();
+            if left > 0. {
+                mp.dwell_left = Some(left);
             } else {
-                if let Some(ai) = mp.points[0].animation_index {
-                    ca.0 = ai;   
+                mp.dwell_left = None;
+                next_point(&mut mp, &mut t, &mut ca, &mut commands, &mut cycle_w, entity);
+            }
+            continue;
+        }
+        if mp.interpolation == PathInterpolation::CatmullRom && mp.segment.is_none() {
+            mp.segment = Some(mp.build_segment(t.translation));
+        }
+        let remaining = match &mp.segment {
+            Some(segment) => segment.length() - segment.travelled,
+            None => t.translation.distance(mp.current().pos)
+        };
+        let dt = time.delta_seconds();
+        let step = dt * mp.next_speed(remaining, dt, &timeline, &time);
+
+        // steering deflection fades out close to the waypoint so that it is still reached
+        let fade = (remaining / STEERING_FADE).min(1.);
+        let steer = sep.map(|s| s.steering * fade).unwrap_or(Vec3::ZERO);
+
+        let arrived = match mp.interpolation {
+            PathInterpolation::Linear => {
+                let delta = mp.current().pos - t.translation;
+                if delta.length_squared() <= step * step {
+                    true
+                } else {
+                    t.translation += step * delta.normalize_or_zero() + steer * dt;
+                    false
                 }
-                if let Some(l_to)  = mp.points[0].look_to  {
-                    t.look_to(l_to, Vec3::Y);
+            },
+            PathInterpolation::CatmullRom => {
+                let segment = mp.segment.as_mut().unwrap();
+                segment.travelled += step;
+                if segment.travelled >= segment.length() {
+                    true
                 } else {
-                    t.look_at(mp.points[0].pos, Vec3::Y);
+                    let (pos, tangent) = segment.sample(segment.travelled);
+                    mp.offset = (mp.offset + steer * dt).lerp(Vec3::ZERO, (dt * OFFSET_RECOVERY).min(1.));
+                    t.translation = pos + mp.offset * fade;
+                    if mp.current().look_to.is_none() && tangent.length_squared() > f32::EPSILON {
+                        let target = t.looking_to(tangent, Vec3::Y).rotation;
+                        mp.face(&mut t, target);
+                    }
+                    false
                 }
-                
             }
-        } else {
-            t.translation += step * delta.normalize();
+        };
+
+        if arrived {
+            let pe = *mp.current();
+            t.translation = pe.pos;
+            reached_w.send(WaypointReached(entity, mp.cursor));
+            mp.prev = Some(pe.pos);
+            mp.segment = None;
+            mp.offset = Vec3::ZERO;
+
+            if let Some(dwell) = pe.dwell {
+                mp.dwell_left = Some(dwell.duration);
+                if let (Some(ai), Some(ca)) = (dwell.animation, ca.as_mut()) {
+                    ca.0 = ai;
+                }
+                if let Some(l_to) = dwell.look_to {
+                    let target = t.looking_to(l_to, Vec3::Y).rotation;
+                    mp.face(&mut t, target);
+                }
+            } else {
+                next_point(&mut mp, &mut t, &mut ca, &mut commands, &mut cycle_w, entity);
+            }
         }
     }
 }
 
 // ---
 
-// #[allow(dead_code)]
-// pub fn debug_moving<M: Component>(
-//     objects_q: Query<(&mut Transform, &mut MovementPath), With<M>>,
-//     mut gizmos: Gizmos
-// ) {
-//     for (gt, mp) in &objects_q {
-//         if mp.points.len() > 0 {
-//             gizmos.line(gt.translation, mp.points[0].pos, Color::srgb(0., 1., 0.));
-//         }
-//     }
-// }
\ No newline at end of file
+fn next_point(
+    mp: &mut MovementPath,
+    t: &mut Transform,
+    ca: &mut Option<Mut<CurrentAnimation>>,
+    commands: &mut Commands,
+    cycle_w: &mut EventWriter<MovementCycleDone>,
+    entity: Entity
+) {
+    let Some((next, forward)) = mp.following() else {
+        if let (Some(fa), Some(ca)) = (mp.finish_animation, ca.as_mut()) {
+            ca.0 = fa;
+        }
+        mp.completed = true;
+        commands.entity(entity).remove::<MovementPath>();
+        
+        if let Some(look_at) = mp.finish_look_at {
+            let target = t.looking_at(look_at, Vec3::Y).rotation;
+            if let Some(speed) = mp.turn_speed {
+                commands.entity(entity).insert(Turning {target, speed});
+            } else {
+                t.rotation = target;
+            }
+        }
+        return;
+    };
+
+    let cycle_done = match mp.mode {
+        PathMode::Once => false,
+        PathMode::Loop => next == 0,
+        PathMode::PingPong => forward && !mp.forward
+    };
+    if cycle_done {
+        mp.cycles += 1;
+        cycle_w.send(MovementCycleDone(entity, mp.cycles));
+    }
+    mp.cursor = next;
+    mp.forward = forward;
+
+    let pe = *mp.current();
+    if pe.easing.eases_in() {
+        mp.speed = 0.;
+    }
+    if let (Some(ai), Some(ca)) = (pe.animation, ca.as_mut()) {
+        ca.0 = ai;   
+    }
+    if let Some(target) = facing(t, &pe) {
+        mp.face(t, target);
+    }
+}
+
+// rotation towards a point, None when already standing on it (a path starting with a dwell in place)
+
+fn facing(t: &Transform, pe: &PathElement) -> Option<Quat> {
+    if let Some(l_to) = pe.look_to {
+        return Some(t.looking_to(l_to, Vec3::Y).rotation);
+    }
+    let dir = pe.pos - t.translation;
+    (dir.with_y(0.).length_squared() > f32::EPSILON).then(|| t.looking_to(dir, Vec3::Y).rotation)
+}
+
+// ---
+
+fn do_turning(
+    mut turning_q: Query<(&mut Transform, &Turning, Entity)>,
+    mut commands: Commands,
+    time: Res<Time>
+) {
+    for (mut t, turning, entity) in turning_q.iter_mut() {
+        t.rotation = turn_towards(t.rotation, turning.target, turning.speed * time.delta_seconds());
+        if t.rotation == turning.target {
+            commands.entity(entity).remove::<Turning>();
+        }
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::ManualEventReader;
+    use crate::clock::ClockPlugin;
+
+    const DT: f32 = 1. / 60.;
+
+    // a headless show on the fixed clock with a single walker on `path`
+
+    fn walker(t: Transform, path: MovementPathBuilder) -> (App, Entity) {
+        let mut app = App::new();
+        app
+        .add_plugins((MinimalPlugins, ClockPlugin {seed: Some(0)}, MovementPlugin))
+        .init_resource::<MusicTimeline>()
+        ;
+        app.update();
+        let e = app.world_mut().spawn(t).id();
+        app.world_mut().commands().entity(e).start_path(path);
+        app.world_mut().flush();
+        (app, e)
+    }
+
+    fn walking(app: &App, e: Entity) -> bool {
+        app.world().get::<MovementPath>(e).is_some() || app.world().get::<Turning>(e).is_some()
+    }
+
+    fn read<E: Event + Indexed>(app: &App, reader: &mut ManualEventReader<E>) -> Vec<usize> {
+        reader.read(app.world().resource::<Events<E>>()).map(|e| e.index()).collect()
+    }
+
+    trait Indexed {
+        fn index(&self) -> usize;
+    }
+
+    impl Indexed for WaypointReached {
+        fn index(&self) -> usize {
+            self.1
+        }
+    }
+
+    impl Indexed for MovementCycleDone {
+        fn index(&self) -> usize {
+            self.1
+        }
+    }
+
+    #[test]
+    fn curve_keeps_its_velocity() {
+        let points = [Vec3::new(4., 0., 0.), Vec3::new(4., 0., 4.), Vec3::new(0., 0., 6.), Vec3::new(-4., 0., 2.)];
+        let path = points.iter()
+            .fold(MovementPath::builder(), |path, p| path.to(*p, 2.))
+            .interpolation(PathInterpolation::CatmullRom);
+        let (mut app, e) = walker(Transform::default(), path);
+        let mut reached = ManualEventReader::<WaypointReached>::default();
+
+        let mut last = Vec3::ZERO;
+        let mut steps = Vec::new();
+        while walking(&app, e) && steps.len() < 2000 {
+            app.update();
+            let pos = app.world().get::<Transform>(e).unwrap().translation;
+            // the step that reaches a waypoint stops on it
+            if read(&app, &mut reached).is_empty() {
+                steps.push(pos.distance(last));
+            }
+            last = pos;
+        }
+        assert_eq!(last, points[3]);
+        assert!(steps.len() > 300);
+        for step in steps {
+            assert!((step / DT - 2.).abs() < 0.05, "{} m/s along the curve", step / DT);
+        }
+    }
+
+    #[test]
+    fn eased_arrival_slows_down_to_the_point() {
+        let to = Vec3::new(0., 0., -10.);
+        let path = MovementPath::builder().to(to, 4.).easing(Easing::InOut);
+        let (mut app, e) = walker(Transform::default(), path);
+
+        let mut speeds = Vec::new();
+        let mut last = Vec3::ZERO;
+        while walking(&app, e) && speeds.len() < 2000 {
+            app.update();
+            let pos = app.world().get::<Transform>(e).unwrap().translation;
+            speeds.push((pos.distance(last) / DT, pos.distance(to)));
+            last = pos;
+        }
+        assert_eq!(last, to);
+
+        // from a standstill up to the velocity
+        assert!(speeds[0].0 <= DEFAULT_ACCELERATION * DT + 1e-3);
+        assert!(speeds.iter().any(|(v, _)| (v - 4.).abs() < 1e-3));
+        // the last meter only slows down, down to a crawl on the point
+        let last_meter: Vec<f32> = speeds.iter().filter(|(_, left)| *left < 1.).map(|(v, _)| *v).collect();
+        assert!(last_meter.windows(2).all(|w| w[1] <= w[0] + 1e-3));
+        assert!(*last_meter.last().unwrap() <= MIN_EASED_SPEED + 1e-3);
+    }
+
+    #[test]
+    fn ping_pong_turns_back_at_the_ends() {
+        let path = MovementPath::builder()
+            .to(Vec3::ZERO, 10.)
+            .to(Vec3::X, 10.)
+            .to(Vec3::X * 2., 10.)
+            .mode(PathMode::PingPong);
+        let (mut app, e) = walker(Transform::default(), path);
+        let (mut reached_r, mut cycles_r) = (ManualEventReader::default(), ManualEventReader::default());
+
+        let (mut reached, mut cycles) = (Vec::new(), Vec::new());
+        while reached.len() < 9 {
+            app.update();
+            reached.extend(read::<WaypointReached>(&app, &mut reached_r));
+            cycles.extend(read::<MovementCycleDone>(&app, &mut cycles_r));
+        }
+        assert_eq!(reached[.. 9], [0, 1, 2, 1, 0, 1, 2, 1, 0]);
+        // one cycle per round trip, counted when it sets off again
+        assert_eq!(cycles, [1, 2]);
+        assert!(app.world().get::<MovementPath>(e).is_some());
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let path = MovementPath::builder()
+            .to(Vec3::ZERO, 10.)
+            .to(Vec3::X, 10.)
+            .to(Vec3::Z, 10.)
+            .mode(PathMode::Loop);
+        let (mut app, _) = walker(Transform::default(), path);
+        let (mut reached_r, mut cycles_r) = (ManualEventReader::default(), ManualEventReader::default());
+
+        let (mut reached, mut cycles) = (Vec::new(), Vec::new());
+        while reached.len() < 7 {
+            app.update();
+            reached.extend(read::<WaypointReached>(&app, &mut reached_r));
+            cycles.extend(read::<MovementCycleDone>(&app, &mut cycles_r));
+        }
+        assert_eq!(reached[.. 7], [0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(cycles, [1, 2]);
+    }
+
+    #[test]
+    fn turning_is_limited_by_turn_speed() {
+        let path = MovementPath::builder()
+            .to(Vec3::new(0., 0., 5.), 3.)
+            .to(Vec3::new(5., 0., 5.), 3.)
+            .turn_speed(2.)
+            .finish_look_at(Vec3::new(-10., 0., 0.));
+        let (mut app, e) = walker(Transform::default(), path);
+
+        let mut last = Quat::IDENTITY;
+        let mut turns = Vec::new();
+        while walking(&app, e) && turns.len() < 2000 {
+            app.update();
+            let rotation = app.world().get::<Transform>(e).unwrap().rotation;
+            turns.push(last.angle_between(rotation));
+            last = rotation;
+        }
+        let facing = app.world().get::<Transform>(e).unwrap().forward();
+        assert!(facing.dot((Vec3::new(-10., 0., 0.) - Vec3::new(5., 0., 5.)).normalize()) > 0.999);
+        // turning around at the start takes half a turn at 2 rad/s
+        assert!(turns.iter().take_while(|a| **a > 1e-3).count() as f32 * DT > 1.5);
+        for a in turns {
+            assert!(a <= 2. * DT + 1e-3, "turned {} rad in a step", a);
+        }
+    }
+
+    #[test]
+    fn ground_speed_leaves_out_dwells_and_climbs() {
+        let path = MovementPath::builder()
+            .to(Vec3::new(0., 0., -1.), 3.)
+            .dwell(Dwell {duration: 1., animation: None, look_to: None})
+            .to(Vec3::new(0., 4., -1.), 2.);
+        let (mut app, e) = walker(Transform::default(), path);
+        let ground_speed = |app: &App| {
+            let pos = app.world().get::<Transform>(e).unwrap().translation;
+            app.world().get::<MovementPath>(e).unwrap().ground_speed(pos)
+        };
+        assert_eq!(ground_speed(&app), 0.);
+        app.update();
+        assert_eq!(ground_speed(&app), 3.);
+        // on the point, standing out the dwell
+        for _ in 0 .. 30 {
+            app.update();
+        }
+        assert_eq!(ground_speed(&app), 0.);
+        // going straight up
+        for _ in 0 .. 60 {
+            app.update();
+        }
+        assert!(app.world().get::<Transform>(e).unwrap().translation.y > 0.);
+        assert_eq!(ground_speed(&app), 0.);
+    }
+
+    // walks `path` to its single point with the music started at 0, the song time of the arrival and the fastest step
+
+    fn arrival(path: MovementPathBuilder) -> (f32, f32) {
+        let (mut app, e) = walker(Transform::default(), path);
+        app.world_mut().resource_mut::<MusicTimeline>().start(0.);
+        let mut reached = ManualEventReader::<WaypointReached>::default();
+        let (mut last, mut fastest) = (Vec3::ZERO, 0f32);
+        for _ in 0 .. 2000 {
+            app.update();
+            let pos = app.world().get::<Transform>(e).unwrap().translation;
+            fastest = fastest.max(pos.distance(last) / DT);
+            last = pos;
+            if !read(&app, &mut reached).is_empty() {
+                let time = app.world().resource::<Time<Fixed>>();
+                return (app.world().resource::<MusicTimeline>().position(&time.as_generic()).unwrap(), fastest);
+            }
+        }
+        panic!("never arrived");
+    }
+
+    #[test]
+    fn arrives_on_time() {
+        let (at, fastest) = arrival(MovementPath::builder().to(Vec3::new(0., 0., -10.), 2.).arrive_at(7.));
+        assert!((at - 7.).abs() <= DT + 1e-3, "arrived at {at}");
+        assert!(fastest < 2.);
+    }
+
+    #[test]
+    fn arrives_on_the_beat() {
+        let beat = MusicTimeline::from_world(&mut World::new()).beat_time(12);
+        let (at, _) = arrival(MovementPath::builder().to(Vec3::new(0., 0., -10.), 2.).arrive_on_beat(12));
+        assert!((at - beat).abs() <= DT + 1e-3, "arrived at {at}, the beat is at {beat}");
+    }
+
+    #[test]
+    fn late_walker_catches_up_at_most_three_times_faster() {
+        let (at, fastest) = arrival(MovementPath::builder().to(Vec3::new(0., 0., -10.), 2.).arrive_at(1.));
+        assert!(fastest <= 2. * MAX_CATCH_UP + 1e-3, "{fastest} m/s");
+        assert!((at - 10. / (2. * MAX_CATCH_UP)).abs() <= 2. * DT, "arrived at {at}");
+    }
+
+    fn error(builder: MovementPathBuilder) -> Option<MovementPathError> {
+        builder.build().err()
+    }
+
+    #[test]
+    fn valid_path_builds() {
+        let path = MovementPath::builder()
+            .to(Vec3::ZERO, 1.).animation(1)
+            .to(Vec3::X, 2.).dwell(Dwell {duration: 0., animation: None, look_to: None})
+            .turn_speed(4.)
+            .build();
+        assert!(path.is_ok());
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(error(MovementPath::builder()), Some(MovementPathError::Empty));
+    }
+
+    #[test]
+    fn step_before_point() {
+        let builder = MovementPath::builder().animation(1).to(Vec3::ZERO, 1.);
+        assert_eq!(error(builder), Some(MovementPathError::StepBeforePoint));
+    }
+
+    #[test]
+    fn invalid_position() {
+        let builder = MovementPath::builder().to(Vec3::ZERO, 1.).to(Vec3::NAN, 1.);
+        assert_eq!(error(builder), Some(MovementPathError::InvalidPosition(1)));
+    }
+
+    #[test]
+    fn invalid_velocity() {
+        for velocity in [0., -1., f32::INFINITY, f32::NAN] {
+            let builder = MovementPath::builder().to(Vec3::ZERO, 1.).to(Vec3::X, velocity);
+            assert_eq!(error(builder), Some(MovementPathError::InvalidVelocity(1)));
+        }
+    }
+
+    #[test]
+    fn too_short_for_cycle() {
+        for mode in [PathMode::Loop, PathMode::PingPong] {
+            let builder = MovementPath::builder().to(Vec3::ZERO, 1.).mode(mode);
+            assert_eq!(error(builder), Some(MovementPathError::TooShortForCycle));
+        }
+        assert!(MovementPath::builder().to(Vec3::ZERO, 1.).to(Vec3::X, 1.).mode(PathMode::Loop).build().is_ok());
+    }
+
+    #[test]
+    fn invalid_turn_speed() {
+        for speed in [0., -1., f32::NAN] {
+            let builder = MovementPath::builder().to(Vec3::ZERO, 1.).turn_speed(speed);
+            assert_eq!(error(builder), Some(MovementPathError::InvalidTurnSpeed));
+        }
+    }
+
+    #[test]
+    fn invalid_dwell() {
+        for duration in [-1., f32::NAN] {
+            let builder = MovementPath::builder()
+                .to(Vec3::ZERO, 1.).dwell(Dwell {duration, animation: None, look_to: None});
+            assert_eq!(error(builder), Some(MovementPathError::InvalidDwell(0)));
+        }
+    }
+
+    #[test]
+    fn invalid_arrival() {
+        for t in [f32::NAN, f32::INFINITY] {
+            let builder = MovementPath::builder().to(Vec3::ZERO, 1.).to(Vec3::X, 1.).arrive_at(t);
+            assert_eq!(error(builder), Some(MovementPathError::InvalidArrival(1)));
+        }
+    }
+}
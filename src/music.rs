@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use bevy::{audio::PlaybackMode, prelude::*, time::common_conditions::once_after_delay};
-use crate::GameState;
+use crate::{clock::{ShowTime, ShowUpdate}, GameState};
 pub struct MusicPlugin;
 impl Plugin for MusicPlugin {
     fn build(&self, app: &mut App) {
@@ -11,7 +11,7 @@ impl Plugin for MusicPlugin {
             .run_if(once_after_delay(Duration::from_secs(15)))
             .run_if(in_state(GameState::Intro))
         )
-        .add_systems(FixedUpdate, timeline.run_if(in_state(MusicState::Playing)))
+        .add_systems(ShowUpdate, timeline.run_if(in_state(MusicState::Playing)))
         .init_resource::<MusicTimeline>()
         .init_state::<MusicState>()
         ;
@@ -34,13 +34,45 @@ pub struct MusicEvent(pub GameState, pub usize);
 #[derive(Resource)]
 pub struct MusicTimeline {
     start: f32,
+    playing: bool,
     events: Vec<(f32, bool, MusicEvent)>
 }
 
+// the beat grid of irm.ogg, checked against the hand timed cues that fall on a beat (see tests)
+const SONG_BPM: f32 = 136.;
+const FIRST_BEAT: f32 = 0.138;
+
+impl MusicTimeline {
+    pub fn start(&mut self, at: f32) {
+        self.start = at;
+        self.playing = true;
+    }
+
+    // song time in seconds, None until the music starts
+
+    pub fn position(&self, time: &Time) -> Option<f32> {
+        self.playing.then(|| time.elapsed_seconds() - self.start)
+    }
+
+    pub fn beat_time(&self, beat: u32) -> f32 {
+        FIRST_BEAT + beat as f32 * 60. / SONG_BPM
+    }
+
+    pub fn beat_at(&self, t: f32) -> f32 {
+        (t - FIRST_BEAT) * SONG_BPM / 60.
+    }
+
+    // song time of the first cue after `t`
+    pub fn next_cue(&self, t: f32) -> Option<f32> {
+        self.events.iter().map(|e| e.0).filter(|at| *at > t).reduce(f32::min)
+    }
+}
+
 impl FromWorld for MusicTimeline {
     fn from_world(_world: &mut World) -> Self {
         MusicTimeline {
             start: 0.,
+            playing: false,
             events: vec![
                 (3.7, false, MusicEvent(GameState::Portal, 0)),    // screw start
                 (10.8, false, MusicEvent(GameState::Portal, 1)),         // camera to leader  leader light more 
@@ -97,13 +129,13 @@ fn load(
 fn play(
     sink_q : Query<&AudioSink>,
     mut timeline: ResMut<MusicTimeline>,
-    time: Res<Time>,
+    time: ShowTime,
     mut next: ResMut<NextState<MusicState>>,
     mut next_g: ResMut<NextState<GameState>>,
 ) {
     if let Ok(sink) = sink_q.get_single() {
         sink.play();
-        timeline.start = time.elapsed_seconds();
+        timeline.start(time.now().elapsed_seconds());
         next.set(MusicState::Playing);
         next_g.set(GameState::Thunder);
     }
@@ -130,4 +162,22 @@ fn timeline (
             // println!("{:?}", e.2);
         } 
     }    
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cues_on_the_beat_grid() {
+        let tl = MusicTimeline::from_world(&mut World::new());
+        // the song drop, "the 10:30" and the rain come in on the beat
+        for cue in [MusicEvent(GameState::Sing, 0), MusicEvent(GameState::Sing, 4), MusicEvent(GameState::Raining, 0)] {
+            let (t, ..) = tl.events.iter().find(|e| e.2 == cue).unwrap();
+            let beat = tl.beat_at(*t);
+            assert!((tl.beat_time(beat.round() as u32) - t).abs() < 0.025, "{cue:?} at beat {beat}");
+        }
+        assert_eq!(tl.next_cue(45.138), Some(46.1));
+        assert_eq!(tl.next_cue(160.), None);
+    }
+}
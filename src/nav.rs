@@ -0,0 +1,292 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::prelude::*;
+
+pub struct NavPlugin;
+impl Plugin for NavPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(PostStartup, build_grid)
+        ;
+    }
+}
+
+// ---
+
+// footprint of a prop on the ground, half size along its local x and z
+
+#[derive(Component, Clone, Copy)]
+pub struct Obstacle(pub Vec2);
+
+#[derive(Resource)]
+pub struct NavGrid {
+    origin: Vec2,
+    cell: f32,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>
+}
+
+const GRID_HALF_EXTENT: f32 = 80.;
+const GRID_CELL: f32 = 0.5;
+const CLEARANCE: f32 = 0.4;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// ---
+
+impl NavGrid {
+    pub fn new(half_extent: f32, cell: f32) -> Self {
+        let width = (2. * half_extent / cell).ceil() as usize;
+        NavGrid {
+            origin: Vec2::splat(-half_extent),
+            cell,
+            width,
+            height: width,
+            blocked: vec![false; width * width]
+        }
+    }
+
+    fn idx(&self, c: (usize, usize)) -> usize {
+        c.1 * self.width + c.0
+    }
+
+    fn to_cell(&self, p: Vec3) -> (usize, usize) {
+        let local = (p.xz() - self.origin) / self.cell;
+        (
+            (local.x.floor().max(0.) as usize).min(self.width - 1),
+            (local.y.floor().max(0.) as usize).min(self.height - 1)
+        )
+    }
+
+    fn center(&self, c: (usize, usize)) -> Vec2 {
+        self.origin + (Vec2::new(c.0 as f32, c.1 as f32) + 0.5) * self.cell
+    }
+
+    pub fn block(&mut self, t: &Transform, half: Vec2) {
+        let half = half + CLEARANCE;
+        let right = t.right().xz().normalize_or_zero();
+        let back = t.back().xz().normalize_or_zero();
+        let reach = Vec2::new(
+            (right.x * half.x).abs() + (back.x * half.y).abs(),
+            (right.y * half.x).abs() + (back.y * half.y).abs()
+        );
+        let center = t.translation.xz();
+        let (x0, y0) = self.to_cell((center - reach).extend(0.).xzy());
+        let (x1, y1) = self.to_cell((center + reach).extend(0.).xzy());
+        for y in y0 ..= y1 {
+            for x in x0 ..= x1 {
+                let d = self.center((x, y)) - center;
+                if d.dot(right).abs() <= half.x && d.dot(back).abs() <= half.y {
+                    let i = self.idx((x, y));
+                    self.blocked[i] = true;
+                }
+            }
+        }
+    }
+
+    pub fn is_walkable(&self, p: Vec3) -> bool {
+        !self.blocked[self.idx(self.to_cell(p))]
+    }
+
+    fn nearest_walkable(&self, c: (usize, usize)) -> Option<(usize, usize)> {
+        let max_ring = self.width.max(self.height);
+        for r in 0 .. max_ring as i64 {
+            for dy in -r ..= r {
+                for dx in -r ..= r {
+                    if dx.abs() != r && dy.abs() != r {
+                        continue;
+                    }
+                    let (x, y) = (c.0 as i64 + dx, c.1 as i64 + dy);
+                    if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+                        continue;
+                    }
+                    let n = (x as usize, y as usize);
+                    if !self.blocked[self.idx(n)] {
+                        return Some(n);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn line_of_sight(&self, a: Vec2, b: Vec2) -> bool {
+        let steps = (a.distance(b) / (self.cell * 0.5)).ceil() as usize;
+        (0 ..= steps).all(|i| {
+            let p = a.lerp(b, i as f32 / steps.max(1) as f32);
+            self.is_walkable(p.extend(0.).xzy())
+        })
+    }
+
+    // A* over 8-connected cells, the result is string-pulled and ends exactly at `to`,
+    // the start point itself is not included
+
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.nearest_walkable(self.to_cell(from))?;
+        let goal = self.nearest_walkable(self.to_cell(to))?;
+
+        let heuristic = |c: (usize, usize)| {
+            let dx = c.0.abs_diff(goal.0) as u32;
+            let dy = c.1.abs_diff(goal.1) as u32;
+            STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+        };
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[self.idx(start)] = 0;
+        open.push(Reverse((heuristic(start), self.idx(start))));
+
+        while let Some(Reverse((_, i))) = open.pop() {
+            let c = (i % self.width, i / self.width);
+            if c == goal {
+                break;
+            }
+            for dy in -1_i64 ..= 1 {
+                for dx in -1_i64 ..= 1 {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    let (x, y) = (c.0 as i64 + dx, c.1 as i64 + dy);
+                    if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+                        continue;
+                    }
+                    let n = (x as usize, y as usize);
+                    let ni = self.idx(n);
+                    if self.blocked[ni] {
+                        continue;
+                    }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal && (self.blocked[self.idx((n.0, c.1))] || self.blocked[self.idx((c.0, n.1))]) {
+                        continue;
+                    }
+                    let nc = cost[i] + if diagonal {DIAGONAL_COST} else {STRAIGHT_COST};
+                    if nc < cost[ni] {
+                        cost[ni] = nc;
+                        came_from[ni] = i;
+                        open.push(Reverse((nc + heuristic(n), ni)));
+                    }
+                }
+            }
+        }
+
+        let goal_i = self.idx(goal);
+        if cost[goal_i] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = vec![goal_i];
+        while *cells.last().unwrap() != self.idx(start) {
+            cells.push(came_from[*cells.last().unwrap()]);
+        }
+        cells.reverse();
+
+        let mut corners = Vec::new();
+        let mut anchor = from.xz();
+        let mut k = 0;
+        while k < cells.len() - 1 {
+            let mut far = k + 1;
+            while far + 1 < cells.len() && self.line_of_sight(anchor, self.center((cells[far + 1] % self.width, cells[far + 1] / self.width))) {
+                far += 1;
+            }
+            if far == cells.len() - 1 {
+                break;
+            }
+            anchor = self.center((cells[far] % self.width, cells[far] / self.width));
+            corners.push(anchor.extend(to.y).xzy());
+            k = far;
+        }
+        corners.push(to);
+        Some(corners)
+    }
+}
+
+// ---
+
+fn build_grid(
+    mut cmd: Commands,
+    obstacles_q: Query<(&Transform, &Obstacle)>
+) {
+    let mut grid = NavGrid::new(GRID_HALF_EXTENT, GRID_CELL);
+    for (t, o) in obstacles_q.iter() {
+        grid.block(t, o.0);
+    }
+    cmd.insert_resource(grid);
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(grid: &mut NavGrid, x: f32, z: f32, half: Vec2) {
+        grid.block(&Transform::from_xyz(x, 0., z), half);
+    }
+
+    fn clear(grid: &NavGrid, from: Vec3, path: &[Vec3]) -> bool {
+        std::iter::once(from).chain(path.iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .all(|w| grid.line_of_sight(w[0].xz(), w[1].xz()))
+    }
+
+    #[test]
+    fn goes_around_a_rectangle() {
+        let mut grid = NavGrid::new(10., 0.5);
+        wall(&mut grid, 0., 0., Vec2::new(1., 3.));
+        let (from, to) = (Vec3::new(-5., 0., 0.), Vec3::new(5., 0., 0.));
+
+        let path = grid.find_path(from, to).unwrap();
+        assert!(path.len() > 1);
+        assert!(clear(&grid, from, &path));
+        assert_eq!(*path.last().unwrap(), to);
+    }
+
+    #[test]
+    fn open_ground_is_a_straight_line() {
+        let grid = NavGrid::new(10., 0.5);
+        let to = Vec3::new(3.3, 1., -2.7);
+        assert_eq!(grid.find_path(Vec3::new(-4., 1., 4.), to), Some(vec![to]));
+    }
+
+    #[test]
+    fn start_inside_an_obstacle_snaps_out() {
+        let mut grid = NavGrid::new(10., 0.5);
+        wall(&mut grid, 0., 0., Vec2::new(1., 1.));
+        let from = Vec3::ZERO;
+        assert!(!grid.is_walkable(from));
+
+        let start = grid.nearest_walkable(grid.to_cell(from)).unwrap();
+        assert!(!grid.blocked[grid.idx(start)]);
+        let to = Vec3::new(6., 0., 0.);
+        let path = grid.find_path(from, to).unwrap();
+        assert_eq!(*path.last().unwrap(), to);
+    }
+
+    #[test]
+    fn goal_inside_an_obstacle_snaps_out() {
+        let mut grid = NavGrid::new(10., 0.5);
+        wall(&mut grid, 0., 0., Vec2::new(1., 1.));
+        let (from, to) = (Vec3::new(-6., 0., 0.), Vec3::ZERO);
+
+        let path = grid.find_path(from, to).unwrap();
+        assert_eq!(*path.last().unwrap(), to);
+        assert!(path[.. path.len() - 1].iter().all(|p| grid.is_walkable(*p)));
+    }
+
+    #[test]
+    fn enclosed_goal_is_unreachable() {
+        let mut grid = NavGrid::new(10., 0.5);
+        wall(&mut grid, 0., 3., Vec2::new(3.5, 0.5));
+        wall(&mut grid, 0., -3., Vec2::new(3.5, 0.5));
+        wall(&mut grid, 3., 0., Vec2::new(0.5, 3.5));
+        wall(&mut grid, -3., 0., Vec2::new(0.5, 3.5));
+        let to = Vec3::ZERO;
+        assert!(grid.is_walkable(to));
+
+        assert_eq!(grid.find_path(Vec3::new(-8., 0., -8.), to), None);
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use crate::shared::min_cost_assignment;
+
+// ---
+
+// left over when one side outnumbers the other
+
+#[derive(Component)]
+pub struct Unpaired;
+
+// minimum total distance pairing between two groups, measured on the ground plane,
+// the result is (a, b) pairs and the extras of the bigger group
+
+pub fn pair_up(a: &[(Entity, Vec3)], b: &[(Entity, Vec3)]) -> (Vec<(Entity, Entity)>, Vec<Entity>) {
+    if a.len() > b.len() {
+        let (pairs, extras) = pair_up(b, a);
+        return (pairs.into_iter().map(|(eb, ea)| (ea, eb)).collect(), extras);
+    }
+    let costs: Vec<Vec<f32>> = a.iter()
+        .map(|(_, pa)| b.iter().map(|(_, pb)| pa.xz().distance(pb.xz())).collect())
+        .collect();
+    let picked = min_cost_assignment(&costs);
+    let mut taken = vec![false; b.len()];
+    let pairs = picked.iter()
+        .zip(a)
+        .map(|(&j, (ea, _))| {
+            taken[j] = true;
+            (*ea, b[j].0)
+        })
+        .collect();
+    let extras = b.iter()
+        .zip(taken)
+        .filter(|(_, t)| !t)
+        .map(|((e, _), _)| *e)
+        .collect();
+    (pairs, extras)
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(first: u32, xs: &[f32], y: f32) -> Vec<(Entity, Vec3)> {
+        xs.iter().enumerate().map(|(i, x)| (Entity::from_raw(first + i as u32), Vec3::new(*x, y, 0.))).collect()
+    }
+
+    #[test]
+    fn height_does_not_count() {
+        // with the men 118 m up a plain 3D distance would pick the crossing pairs
+        let at = |i: u32, x: f32, y: f32, z: f32| (Entity::from_raw(i), Vec3::new(x, y, z));
+        let men = [at(0, -4., 118., -8.), at(1, 10., 118., -5.)];
+        let girls = [at(10, 8., 0., 4.), at(11, 8., 0., -6.)];
+        let (pairs, extras) = pair_up(&men, &girls);
+        assert!(extras.is_empty());
+        assert!(pairs.contains(&(men[0].0, girls[0].0)));
+        assert!(pairs.contains(&(men[1].0, girls[1].0)));
+    }
+
+    #[test]
+    fn extra_girls() {
+        let men = group(0, &[0., 10.], 0.);
+        let girls = group(10, &[-20., 1., 30., 9.], 0.);
+        let (pairs, extras) = pair_up(&men, &girls);
+        assert_eq!(pairs, vec![(men[0].0, girls[1].0), (men[1].0, girls[3].0)]);
+        assert_eq!(extras, vec![girls[0].0, girls[2].0]);
+    }
+
+    #[test]
+    fn extra_men() {
+        let men = group(0, &[0., 5., 10.], 0.);
+        let girls = group(10, &[11., 1.], 0.);
+        let (pairs, extras) = pair_up(&men, &girls);
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.contains(&(men[0].0, girls[1].0)));
+        assert!(pairs.contains(&(men[2].0, girls[0].0)));
+        assert_eq!(extras, vec![men[1].0]);
+    }
+
+    #[test]
+    fn nobody_on_one_side() {
+        let men = group(0, &[0., 5.], 0.);
+        let (pairs, extras) = pair_up(&men, &[]);
+        assert!(pairs.is_empty());
+        assert_eq!(extras, vec![men[0].0, men[1].0]);
+    }
+
+    fn total(pairs: &[(Entity, Entity)], men: &[(Entity, Vec3)], girls: &[(Entity, Vec3)]) -> f32 {
+        let pos = |all: &[(Entity, Vec3)], e: Entity| all.iter().find(|(a, _)| *a == e).unwrap().1;
+        pairs.iter().map(|(m, g)| pos(men, *m).xz().distance(pos(girls, *g).xz())).sum()
+    }
+
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+        permutations(n - 1).into_iter()
+            .flat_map(|p| (0 ..= p.len()).map(move |i| {
+                let mut q = p.clone();
+                q.insert(i, n - 1);
+                q
+            }))
+            .collect()
+    }
+
+    #[test]
+    fn globally_optimal() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        for _ in 0 .. 20 {
+            let mut at = |first: u32| -> Vec<(Entity, Vec3)> {
+                (0 .. 6).map(|i| (Entity::from_raw(first + i), Vec3::new(rng.f32() * 40., 0., rng.f32() * 40.))).collect()
+            };
+            let (men, girls) = (at(0), at(10));
+            let (pairs, _) = pair_up(&men, &girls);
+            let best = permutations(girls.len()).into_iter()
+                .map(|p| p.iter().zip(&men).map(|(j, m)| m.1.xz().distance(girls[*j].1.xz())).sum::<f32>())
+                .fold(f32::INFINITY, f32::min);
+            assert!((total(&pairs, &men, &girls) - best).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn all_at_once_beats_one_by_one() {
+        // the first man on his own takes the girl the second one needs
+        let men = group(0, &[0., 2.], 0.);
+        let girls = group(10, &[0.9, -1.], 0.);
+        let (first, _) = pair_up(&men[.. 1], &girls);
+        let (second, _) = pair_up(&men[1 ..], &girls[1 ..]);
+        assert_eq!(first, vec![(men[0].0, girls[0].0)]);
+        assert!((total(&[first, second].concat(), &men, &girls) - 3.9).abs() < 1e-3);
+
+        let (pairs, _) = pair_up(&men, &girls);
+        assert!((total(&pairs, &men, &girls) - 2.1).abs() < 1e-3);
+    }
+}
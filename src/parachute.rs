@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+
+use crate::{
+    animator::{Clip, CurrentAnimation},
+    clock::ShowUpdate,
+    falling::{fall, Falling, Landed, Wind, GROUND_Y, TERMINAL_VELOCITY},
+    girls::Girl,
+    men::Pair
+};
+
+pub struct ParachutePlugin;
+impl Plugin for ParachutePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<ParachuteDeployed>()
+        .add_systems(Startup, setup)
+        .add_systems(ShowUpdate, (deploy, glide)
+            .chain()
+            .before(fall)
+            .run_if(any_with_component::<Parachute>)
+        )
+        .add_systems(Update, fold.run_if(on_event::<Landed>()))
+        ;
+    }
+}
+
+// ---
+
+// opens once the body drops below `deploy_altitude`, then the descent slows down to `descent_speed`
+// and the body glides towards its pair
+
+#[derive(Component)]
+pub struct Parachute {
+    pub deploy_altitude: f32,
+    pub descent_speed: f32,
+    pub glide_speed: f32,
+    pub animation: Option<Clip>,
+    canopy: Option<Entity>,
+    opened: f32
+}
+
+impl Parachute {
+    pub fn new(deploy_altitude: f32) -> Self {
+        Parachute {
+            deploy_altitude,
+            descent_speed: DESCENT_SPEED,
+            glide_speed: GLIDE_SPEED,
+            animation: None,
+            canopy: None,
+            opened: 0.
+        }
+    }
+
+    pub fn with_animation(mut self, clip: impl Into<Clip>) -> Self {
+        self.animation = Some(clip.into());
+        self
+    }
+}
+
+#[derive(Event)]
+#[allow(dead_code)]
+pub struct ParachuteDeployed(pub Entity);
+
+#[derive(Resource)]
+struct Canopy {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>
+}
+
+const DESCENT_SPEED: f32 = 4.;
+const GLIDE_SPEED: f32 = 6.;
+const DEPLOY_TIME: f32 = 1.5;
+const UPRIGHT_SPEED: f32 = 3.;
+const LANDING_CLEARANCE: f32 = 3.;
+const CANOPY_OFFSET: Vec3 = Vec3::new(0., 4., 0.);
+const CANOPY_SCALE: Vec3 = Vec3::new(2.5, 0.8, 2.5);
+
+// ---
+
+fn setup(
+    mut cmd: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    cmd.insert_resource(Canopy {
+        mesh: meshes.add(Sphere::new(1.).mesh().uv(16, 8)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.9, 0.2, 0.2),
+            cull_mode: None,
+            double_sided: true,
+            ..default()
+        })
+    });
+}
+
+// ---
+
+fn deploy(
+    mut chutes_q: Query<(Entity, &Transform, &mut Parachute, &mut Falling, Option<&mut CurrentAnimation>)>,
+    canopy: Res<Canopy>,
+    mut deployed_w: EventWriter<ParachuteDeployed>,
+    mut cmd: Commands
+) {
+    for (e, t, mut p, mut f, ca) in chutes_q.iter_mut() {
+        if p.canopy.is_some() || t.translation.y > p.deploy_altitude {
+            continue;
+        }
+        f.spin = Vec3::ZERO;
+        if let (Some(mut ca), Some(clip)) = (ca, p.animation) {
+            ca.0 = clip;
+        }
+        let canopy_e = cmd.spawn(PbrBundle {
+            mesh: canopy.mesh.clone(),
+            material: canopy.material.clone(),
+            transform: Transform::from_translation(CANOPY_OFFSET).with_scale(CANOPY_SCALE),
+            ..default()
+        }).id();
+        cmd.entity(e).add_child(canopy_e);
+        p.canopy = Some(canopy_e);
+        deployed_w.send(ParachuteDeployed(e));
+    }
+}
+
+// ---
+
+fn glide(
+    mut chutes_q: Query<(&mut Transform, &mut Parachute, &mut Falling, Option<&Pair>)>,
+    girls_q: Query<&Transform, (With<Girl>, Without<Parachute>)>,
+    wind: Res<Wind>,
+    time: Res<Time>
+) {
+    let dt = time.delta_seconds();
+    for (mut t, mut p, mut f, pair) in chutes_q.iter_mut() {
+        if p.canopy.is_none() {
+            continue;
+        }
+        p.opened = (p.opened + dt / DEPLOY_TIME).min(1.);
+        f.terminal_velocity = TERMINAL_VELOCITY + (p.descent_speed - TERMINAL_VELOCITY) * p.opened;
+
+        // aim a bit short of the girl, at the ground speed that brings him there by touchdown
+        let heading = match pair.and_then(|pair| girls_q.get(pair.0).ok()) {
+            Some(gt) => {
+                let to = (gt.translation - t.translation).with_y(0.);
+                let aim = to - to.normalize_or_zero() * LANDING_CLEARANCE.min(to.length());
+                let time_left = (t.translation.y - GROUND_Y).max(0.) / p.descent_speed;
+                aim / time_left.max(dt)
+            },
+            None => wind.0
+        };
+        f.glide = ((heading - wind.0) * p.opened).clamp_length_max(p.glide_speed);
+
+        let facing = heading.with_y(0.);
+        if facing.length_squared() > f32::EPSILON {
+            let upright = t.looking_to(facing, Vec3::Y).rotation;
+            t.rotation = t.rotation.slerp(upright, (UPRIGHT_SPEED * dt).min(1.));
+        }
+    }
+}
+
+// ---
+
+fn fold(
+    mut landed_r: EventReader<Landed>,
+    chutes_q: Query<&Parachute>,
+    mut cmd: Commands
+) {
+    for e in landed_r.read() {
+        let Ok(p) = chutes_q.get(e.0) else {
+            continue;
+        };
+        if let Some(canopy_e) = p.canopy {
+            cmd.entity(canopy_e).despawn_recursive();
+        }
+        cmd.entity(e.0).remove::<Parachute>();
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::ManualEventReader;
+    use crate::{clock::ClockPlugin, falling::FallingPlugin};
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn opens_slows_down_and_glides_to_the_girl() {
+        let mut app = App::new();
+        app
+        .add_plugins((MinimalPlugins, ClockPlugin {seed: Some(0)}, FallingPlugin, ParachutePlugin))
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<StandardMaterial>>()
+        ;
+        app.update();
+        let girl = app.world_mut().spawn((Transform::from_xyz(30., 0., 10.), Girl)).id();
+        let man = app.world_mut().spawn((
+            Transform::from_xyz(0., 80., 0.),
+            Falling::new(Vec3::ZERO, Vec3::ONE),
+            Parachute::new(40.),
+            Pair(girl)
+        )).id();
+        let mut deployed_r = ManualEventReader::<ParachuteDeployed>::default();
+        let y = |app: &App| app.world().get::<Transform>(man).unwrap().translation.y;
+
+        // opens the step after it drops through the deploy altitude
+        let mut steps = 0;
+        while app.world().get::<Parachute>(man).unwrap().canopy.is_none() && steps < 1000 {
+            app.update();
+            steps += 1;
+        }
+        let canopy = app.world().get::<Parachute>(man).unwrap().canopy.unwrap();
+        assert!(y(&app) <= 40. && y(&app) > 40. - 2. * TERMINAL_VELOCITY * DT, "opened at {}", y(&app));
+        let deployed: Vec<Entity> = deployed_r.read(app.world().resource::<Events<ParachuteDeployed>>()).map(|e| e.0).collect();
+        assert_eq!(deployed, [man]);
+        assert_eq!(app.world().get::<Falling>(man).unwrap().spin, Vec3::ZERO);
+
+        // and slows down to the descent speed once fully open
+        for _ in 0 .. 300 {
+            app.update();
+        }
+        let vy = app.world().get::<Falling>(man).unwrap().velocity.y;
+        assert!((vy + DESCENT_SPEED).abs() < 0.1, "{vy} m/s");
+
+        // landing next to the girl
+        while app.world().get::<Falling>(man).is_some() && steps < 10000 {
+            app.update();
+            steps += 1;
+        }
+        let landed = app.world().get::<Transform>(man).unwrap().translation;
+        let to_girl = landed.xz().distance(Vec2::new(30., 10.));
+        assert!(to_girl < 2. * LANDING_CLEARANCE, "landed {to_girl} m from the girl");
+
+        // the canopy is folded away
+        app.update();
+        assert!(app.world().get::<Parachute>(man).is_none());
+        assert!(app.world().get_entity(canopy).is_none());
+    }
+}
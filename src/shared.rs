@@ -1,15 +1,187 @@
 use bevy::prelude::*;
 
-fn rand_sign() -> f32{
-    let r = fastrand::f32();
+#[derive(Resource)]
+pub struct ShowRng(pub fastrand::Rng);
+
+// the seed ShowRng starts from, systems running while assets are still loading
+// take their numbers from `entity_rng` instead, so the order things finish loading in
+// doesn't change what ShowRng gives the show later
+
+#[derive(Resource, Clone, Copy)]
+pub struct ShowSeed(pub u64);
+
+pub fn entity_rng(seed: ShowSeed, entity: Entity) -> fastrand::Rng {
+    fastrand::Rng::with_seed(seed.0 ^ entity.to_bits().wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+fn rand_sign(rng: &mut fastrand::Rng) -> f32{
+    let r = rng.f32();
     2. * if r > 0.5 {  r - 1. } else { r} 
 }
 
-pub fn random_pos(base: Vec3, quant: f32) -> Vec3 {
-    base + Vec3::Z * rand_sign() * quant + Vec3::X * rand_sign() * quant
+pub fn random_pos(rng: &mut fastrand::Rng, base: Vec3, quant: f32) -> Vec3 {
+    base + Vec3::Z * rand_sign(rng) * quant + Vec3::X * rand_sign(rng) * quant
 } 
 
+// minimum cost assignment of rows to columns (Hungarian method), rows must not outnumber columns,
+// returns the column picked for every row. Costs that are not finite (NaN from a degenerate position)
+// count as worse than any finite one, otherwise the search would never settle
 
+pub fn min_cost_assignment(costs: &[Vec<f32>]) -> Vec<usize> {
+    let n = costs.len();
+    let m = costs.first().map_or(0, |row| row.len());
+    if n == 0 || n > m {
+        return Vec::new();
+    }
+
+    let (lo, hi) = costs.iter().flatten()
+        .filter(|c| c.is_finite())
+        .fold((0_f64, 0_f64), |(lo, hi), c| (lo.min(*c as f64), hi.max(*c as f64)));
+    let worst = hi + (hi - lo) * n as f64 + 1.;
+    let cost = |i: usize, j: usize| {
+        let c = costs[i][j];
+        if c.is_finite() {c as f64} else {worst}
+    };
+
+    let mut u = vec![0_f64; n + 1];
+    let mut v = vec![0_f64; m + 1];
+    let mut owner = vec![0_usize; m + 1];
+    let mut way = vec![0_usize; m + 1];
+
+    for i in 1 ..= n {
+        owner[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[j0] = true;
+            let i0 = owner[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1 ..= m {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if reduced < min_v[j] {
+                    min_v[j] = reduced;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+            for j in 0 ..= m {
+                if used[j] {
+                    u[owner[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if owner[j0] == 0 {
+                break;
+            }
+        }
+        while j0 != 0 {
+            let j1 = way[j0];
+            owner[j0] = owner[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut picked = vec![0; n];
+    for (j, &i) in owner.iter().enumerate().skip(1) {
+        if i != 0 {
+            picked[i - 1] = j - 1;
+        }
+    }
+    picked
+}
 
+// ---
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn total(costs: &[Vec<f32>], picked: &[usize]) -> f32 {
+        picked.iter().enumerate().map(|(i, &j)| costs[i][j]).sum()
+    }
+
+    fn distinct(picked: &[usize]) -> bool {
+        let mut sorted = picked.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        sorted.len() == picked.len()
+    }
+
+    #[test]
+    fn square_optimum() {
+        let costs = vec![
+            vec![4., 1., 3.],
+            vec![2., 0., 5.],
+            vec![3., 2., 2.]
+        ];
+        let picked = min_cost_assignment(&costs);
+        assert_eq!(picked, vec![1, 0, 2]);
+        assert_eq!(total(&costs, &picked), 5.);
+    }
+
+    #[test]
+    fn fewer_rows_than_columns() {
+        let costs = vec![
+            vec![9., 2., 7., 1.],
+            vec![6., 4., 3., 8.]
+        ];
+        let picked = min_cost_assignment(&costs);
+        assert_eq!(picked, vec![3, 2]);
+        assert_eq!(total(&costs, &picked), 4.);
+    }
+
+    #[test]
+    fn more_rows_than_columns_is_empty() {
+        let costs = vec![vec![1., 2.], vec![3., 4.], vec![5., 6.]];
+        assert!(min_cost_assignment(&costs).is_empty());
+        assert!(min_cost_assignment(&[]).is_empty());
+    }
+
+    #[test]
+    fn ties_still_give_an_optimum() {
+        let costs = vec![vec![1.; 3]; 3];
+        let picked = min_cost_assignment(&costs);
+        assert_eq!(picked.len(), 3);
+        assert!(distinct(&picked));
+        assert_eq!(total(&costs, &picked), 3.);
+
+        let costs = vec![
+            vec![1., 1., 5.],
+            vec![1., 1., 5.],
+            vec![5., 5., 2.]
+        ];
+        let picked = min_cost_assignment(&costs);
+        assert!(distinct(&picked));
+        assert_eq!(picked[2], 2);
+        assert_eq!(total(&costs, &picked), 4.);
+    }
+
+    #[test]
+    fn non_finite_costs_are_the_worst() {
+        let costs = vec![
+            vec![f32::NAN, 1., 4.],
+            vec![2., f32::INFINITY, 3.],
+            vec![f32::NEG_INFINITY, 5., f32::NAN]
+        ];
+        let picked = min_cost_assignment(&costs);
+        assert_eq!(picked, vec![2, 0, 1]);
+        assert_eq!(total(&costs, &picked), 11.);
+
+        // all of them bad still gives everyone a column
+        let costs = vec![vec![f32::NAN; 3]; 2];
+        let picked = min_cost_assignment(&costs);
+        assert_eq!(picked.len(), 2);
+        assert!(distinct(&picked));
+    }
+}
@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::{airplane::Aboard, clock::ShowUpdate, falling::Falling, movement::{do_moving, MovementPath}};
+
+pub struct SteeringPlugin;
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(ShowUpdate, separate
+            .before(do_moving)
+            .run_if(any_with_component::<MovementPath>)
+        )
+        ;
+    }
+}
+
+// ---
+
+// boids-style separation, the resulting steering is picked up by do_moving for walking agents only,
+// agents with higher priority give way less, only visible agents on the ground take part,
+// those in the plane, in the air or on a lift above are left out
+
+#[derive(Component, Clone, Copy)]
+pub struct Separation {
+    pub radius: f32,
+    pub priority: f32,
+    pub steering: Vec3
+}
+
+impl Separation {
+    pub fn new(radius: f32, priority: f32) -> Self {
+        Separation {
+            radius,
+            priority,
+            steering: Vec3::ZERO
+        }
+    }
+}
+
+const SEPARATION_STRENGTH: f32 = 5.;
+const MAX_STEERING: f32 = 3.;
+const HEIGHT_BAND: f32 = 1.5;
+const SIDESTEP: f32 = 0.5;
+
+// ---
+
+struct Agent {
+    pos: Vec3,
+    radius: f32,
+    priority: f32
+}
+
+fn cell(pos: Vec3, size: f32) -> IVec2 {
+    IVec2::new((pos.x / size).floor() as i32, (pos.z / size).floor() as i32)
+}
+
+// ---
+
+fn separate(
+    mut agents_q: Query<(Entity, &Transform, &mut Separation, Has<MovementPath>)>,
+    aboard_q: Query<(), With<Aboard>>,
+    falling_q: Query<(), With<Falling>>,
+    visibility_q: Query<&Visibility>
+) {
+    let grounded = |e: Entity| !aboard_q.contains(e) && !falling_q.contains(e) && visibility_q.get(e).map_or(true, |v| *v != Visibility::Hidden);
+    let agents: HashMap<Entity, Agent> = agents_q.iter()
+        .filter(|(e, _, _, _)| grounded(*e))
+        .map(|(e, t, s, _)| (e, Agent {pos: t.translation, radius: s.radius, priority: s.priority}))
+        .collect();
+    let Some(max_radius) = agents.values().map(|a| a.radius).reduce(f32::max) else {
+        return;
+    };
+    let size = 2. * max_radius;
+
+    let mut grid: HashMap<IVec2, Vec<Entity>> = HashMap::new();
+    for (e, a) in agents.iter() {
+        grid.entry(cell(a.pos, size)).or_default().push(*e);
+    }
+
+    for (e, _, mut sep, moving) in agents_q.iter_mut() {
+        let Some(me) = agents.get(&e).filter(|_| moving) else {
+            sep.steering = Vec3::ZERO;
+            continue;
+        };
+        let c = cell(me.pos, size);
+        let mut push = Vec3::ZERO;
+        for dx in -1 ..= 1 {
+            for dz in -1 ..= 1 {
+                let Some(neighbours) = grid.get(&(c + IVec2::new(dx, dz))) else {
+                    continue;
+                };
+                for other_e in neighbours {
+                    if *other_e == e {
+                        continue;
+                    }
+                    let other = &agents[other_e];
+                    let away = me.pos - other.pos;
+                    if away.y.abs() > HEIGHT_BAND {
+                        continue;
+                    }
+                    let away = away.with_y(0.);
+                    let reach = me.radius + other.radius;
+                    let d = away.length();
+                    if d >= reach || d <= f32::EPSILON {
+                        continue;
+                    }
+                    let weight = other.priority / (me.priority + other.priority).max(f32::EPSILON);
+                    // a bit of sidestep as well, both turn the same way around each other
+                    // instead of pushing head on forever when they walk into each other
+                    let away = away / d;
+                    push += (away + Vec3::Y.cross(away) * SIDESTEP) * (1. - d / reach) * weight;
+                }
+            }
+        }
+        sep.steering = (push * SEPARATION_STRENGTH).clamp_length_max(MAX_STEERING);
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::ManualEventReader;
+    use crate::{
+        clock::ClockPlugin,
+        movement::{MovementCommandsExt, MovementPathDone, MovementPlugin},
+        music::MusicTimeline
+    };
+
+    #[test]
+    fn crossing_walkers_keep_apart() {
+        let mut app = App::new();
+        app
+        .add_plugins((MinimalPlugins, ClockPlugin {seed: Some(0)}, MovementPlugin, SteeringPlugin))
+        .init_resource::<MusicTimeline>()
+        ;
+        app.update();
+        // both get to the crossing at the same time
+        let walkers: Vec<Entity> = [(Vec3::new(-5., 0., 0.), Vec3::new(5., 0., 0.)), (Vec3::new(0., 0., -5.), Vec3::new(0., 0., 5.))]
+            .into_iter()
+            .map(|(from, to)| {
+                let e = app.world_mut().spawn((Transform::from_translation(from), Separation::new(0.4, 1.))).id();
+                app.world_mut().commands().entity(e).start_path(MovementPath::builder().to(to, 2.));
+                e
+            })
+            .collect();
+        app.world_mut().flush();
+
+        let mut done_r = ManualEventReader::<MovementPathDone>::default();
+        let mut done = Vec::new();
+        let mut closest = f32::INFINITY;
+        for _ in 0 .. 1000 {
+            app.update();
+            let [a, b] = [walkers[0], walkers[1]].map(|e| app.world().get::<Transform>(e).unwrap().translation);
+            closest = closest.min(a.xz().distance(b.xz()));
+            done.extend(done_r.read(app.world().resource::<Events<MovementPathDone>>()).map(|e| e.0));
+        }
+        assert!(closest >= 0.4, "came within {closest} m");
+        done.sort();
+        assert_eq!(done, walkers);
+    }
+}